@@ -14,12 +14,12 @@ fn main() {
     };
 
     if let Err(e) = rt_println!(format_spec, id="ID", name="NAME", city="CITY") {
-        println!("error in header: {}", e);
+        println!("{}", e.render(&format_spec));
         if let runtime_fmt::Error::BadSyntax(_) = e { return }
     }
     for row in rows() {
         if let Err(e) = rt_println!(format_spec, id=row.id, name=row.name, city=row.city) {
-            println!("error: {}", e);
+            println!("{}", e.render(&format_spec));
             return;
         }
     }
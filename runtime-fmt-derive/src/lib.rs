@@ -7,8 +7,36 @@ extern crate syn;
 
 use proc_macro::TokenStream;
 
-/// Derive a `FormatArgs` implementation for the provided input struct.
-#[proc_macro_derive(FormatArgs)]
+/// Derive a `FormatArgs` implementation for the provided input struct or enum.
+///
+/// The fields of an enum are the union of the fields of its variants. A
+/// prepared format string may refer to any of them, but formatting a value
+/// whose variant lacks one of those fields fails with `Error::MissingField`.
+///
+/// Fields accept the following options in `#[format(...)]` attributes:
+///
+/// * `rename = "name"` refers to the field by the given name rather than by
+///   its identifier. Names may join words with hyphens, as in `user-name`.
+/// * `alias = "name"` also accepts the given name, and may be repeated.
+/// * `skip` makes the field inaccessible to format strings. Skipped fields
+///   are not counted when numbering the fields of tuple structs.
+/// * `flatten`, for a field of a struct whose type implements `FormatArgs`,
+///   makes the names of that type's children refer to them as if they were
+///   fields of the struct itself. The struct's own names take precedence.
+/// * `with = "path::to::function"` formats the field with the given function
+///   rather than with its type's `Display` implementation, which it need not
+///   have. The function takes a reference to the field and a
+///   `&mut fmt::Formatter`, as `Display::fmt` does. Another formatting trait
+///   is chosen by naming it, as in `with(LowerHex = "path::to::function")`.
+///
+/// The struct or enum itself accepts the following options:
+///
+/// * `method = "name"`, which may be repeated, refers by that name to the
+///   result of calling the method of that name with no arguments besides
+///   `&self`. The method is called each time the result is formatted.
+/// * `positional`, for a struct with named fields, also refers to its fields
+///   by index in the order they are declared, as for a tuple struct.
+#[proc_macro_derive(FormatArgs, attributes(format))]
 pub fn derive_format_args(input: TokenStream) -> TokenStream {
     let string = input.to_string();
     let ast = syn::parse_derive_input(&string).unwrap();
@@ -16,136 +44,449 @@ pub fn derive_format_args(input: TokenStream) -> TokenStream {
 }
 
 fn implement(ast: &syn::DeriveInput) -> quote::Tokens {
-    // The rough structure of this (dummy_ident, extern crate/use) is based on
+    // The rough structure of this (dummy const, extern crate/use) is based on
     // how serde_derive does it.
 
     let ident = &ast.ident;
-    let variant = match ast.body {
-        syn::Body::Struct(ref variant) => variant,
-        _ => panic!("#[derive(FormatArgs)] is not implemented for enums")
+    let attrs = type_attrs(ast);
+    let (mut children, flattened) = match ast.body {
+        syn::Body::Struct(ref variant) => struct_children(variant),
+        syn::Body::Enum(_) if attrs.positional => {
+            panic!("#[derive(FormatArgs)] does not support positional access to the fields of enums");
+        }
+        syn::Body::Enum(ref variants) => (enum_children(ident, variants), Vec::new()),
     };
-
-    let dummy_ident = syn::Ident::new(format!("_IMPL_FORMAT_ARGS_FOR_{}", ident));
-
-    let (validate_name, validate_index, get_child, as_usize);
-    match *variant {
-        syn::VariantData::Struct(ref fields) => {
-            get_child = build_fields(fields);
-            as_usize = build_usize(ast, fields);
-            validate_index = quote! { false };
-
-            let index = 0..fields.len();
-            let ident: Vec<_> = fields.iter()
-                .map(|field| field.ident.as_ref().unwrap())
-                .map(ToString::to_string)
-                .collect();
-            validate_name = quote! {
-                match name {
-                    #(#ident => _Option::Some(#index),)*
-                    _ => _Option::None,
-                }
-            };
+    if attrs.positional {
+        for child in &mut children {
+            child.positional = true;
         }
-        syn::VariantData::Tuple(ref fields) => {
-            get_child = build_fields(fields);
-            as_usize = build_usize(ast, fields);
-            validate_name = quote! { _Option::None };
+    }
+    // Children addressed by index come first, so that each index is also the
+    // index of its child. The children of flattened fields follow the
+    // struct's own, each field's in turn.
+    children.sort_by_key(|child| !child.positional);
+    // Computed children follow the fields.
+    let methods = attrs.methods;
+
+    let positional = children.iter().filter(|child| child.positional).count();
+    let own_len = children.len() + methods.len();
 
-            let len = fields.len();
-            validate_index = quote! { index < #len };
+    let mut named: Vec<(&str, usize)> = Vec::new();
+    let child_names = children.iter().map(|child| &child.names[..])
+        .chain(methods.iter().map(::std::slice::from_ref));
+    for (index, names) in child_names.enumerate() {
+        for name in names {
+            if named.iter().any(|&(other, _)| other == name) {
+                panic!("#[derive(FormatArgs)] found more than one field named `{}`", name);
+            }
+            named.push((name, index));
         }
-        syn::VariantData::Unit => {
-            validate_name = quote! { _Option::None };
-            validate_index = quote! { false };
-            get_child = quote! { panic!("bad index {}", index) };
-            as_usize = get_child.clone();
+    }
+    let name = named.iter().map(|&(name, _)| name);
+    let name_index = named.iter().map(|&(_, index)| index);
+    let validate_index = if positional == 0 {
+        quote! { false }
+    } else {
+        quote! { index < #positional }
+    };
+
+    let names = children.iter().map(|child| child.names.first().map_or("", |name| &name[..]))
+        .chain(methods.iter().map(|method| &method[..]));
+    let names = if flattened.is_empty() {
+        quote! { _Cow::Borrowed(&[#(#names),*]) }
+    } else {
+        let flat_ty = flattened.iter().map(|(_, ty)| ty);
+        let flat_ty2 = flat_ty.clone();
+        quote! {
+            let mut names = vec![#(#names),*];
+            #(
+                let inner = <#flat_ty as _runtime_fmt::FormatArgs>::names();
+                names.extend((0..<#flat_ty2 as _runtime_fmt::FormatArgs>::len())
+                    .map(|i| inner.get(i).cloned().unwrap_or("")));
+            )*
+            _Cow::Owned(names)
         }
     };
 
+    let flat_ty: Vec<_> = flattened.iter().map(|(_, ty)| ty).collect();
+    let (flat_ty2, flat_ty3) = (flat_ty.clone(), flat_ty.clone());
+
+    let index: Vec<_> = (0..children.len()).collect();
+    let traits: Vec<_> = children.iter().map(Child::traits).collect();
+    let get: Vec<_> = children.iter().map(|child| child.get(ident)).collect();
+    let index2 = index.clone();
+    let method_index: Vec<_> = (children.len()..own_len).collect();
+    let method_index2 = method_index.clone();
+    let method: Vec<_> = methods.iter().map(|method| syn::Ident::from(&method[..])).collect();
+    let call: Vec<_> = method.iter().map(|method| quote! {
+        |parent, formatter, f| unsafe {
+            _runtime_fmt::codegen::call_computed(Self::#method, parent, formatter, f)
+        }
+    }).collect();
+    let flat_get_child = find_flattened(&flattened, own_len, |_, ty| {
+        quote! { <#ty as _runtime_fmt::FormatArgs>::get_child(rest) }
+    });
+    let flat_get = find_flattened(&flattened, own_len, |member, ty| {
+        quote! { <#ty as _runtime_fmt::FormatArgs>::get(&self.#member, rest) }
+    });
+
     let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
     quote! {
-        #[allow(non_upper_case_globals, unused_attributes)]
+        #[allow(non_upper_case_globals, unused_attributes, unreachable_patterns)]
         #[allow(unused_variables, unused_qualifications)]
-        const #dummy_ident: () = {
+        const _: () = {
             extern crate runtime_fmt as _runtime_fmt;
-            use std::fmt::{Formatter as _Formatter, Result as _Result};
+            use std::borrow::Cow as _Cow;
             use std::option::Option as _Option;
             #[automatically_derived]
-            impl #impl_generics _runtime_fmt::FormatArgs for #ident #ty_generics #where_clause {
+            unsafe impl #impl_generics _runtime_fmt::FormatArgs for #ident #ty_generics #where_clause {
                 fn validate_name(name: &str) -> _Option<usize> {
-                    #validate_name
+                    match name {
+                        #(#name => return _Option::Some(#name_index),)*
+                        _ => {}
+                    }
+                    let base = #own_len;
+                    #(
+                        if let _Option::Some(index) = <#flat_ty as _runtime_fmt::FormatArgs>::validate_name(name) {
+                            return _Option::Some(base + index);
+                        }
+                        let base = base + <#flat_ty2 as _runtime_fmt::FormatArgs>::len();
+                    )*
+                    _Option::None
+                }
+                fn names() -> _Cow<'static, [&'static str]> {
+                    #names
                 }
                 fn validate_index(index: usize) -> bool {
                     #validate_index
                 }
-                fn get_child<__F>(index: usize) -> _Option<fn(&Self, &mut _Formatter) -> _Result>
-                    where __F: _runtime_fmt::codegen::FormatTrait + ?Sized
-                {
-                    #get_child
+                fn len() -> usize {
+                    #own_len #(+ <#flat_ty3 as _runtime_fmt::FormatArgs>::len())*
+                }
+                fn get_child(index: usize) -> _runtime_fmt::codegen::Child {
+                    match index {
+                        #(
+                            #index => _runtime_fmt::codegen::child(#traits),
+                        )*
+                        #(
+                            #method_index => unsafe {
+                                _runtime_fmt::codegen::computed(
+                                    _runtime_fmt::__rt_traits!(returned Self::#method),
+                                    #call,
+                                )
+                            },
+                        )*
+                        _ => {
+                            #flat_get_child
+                            panic!("bad index {}", index)
+                        }
+                    }
                 }
-                fn as_usize(index: usize) -> Option<fn(&Self) -> &usize> {
-                    #as_usize
+                fn get(&self, index: usize) -> _Option<&_runtime_fmt::codegen::Opaque> {
+                    match index {
+                        #(#index2 => #get,)*
+                        #(#method_index2 => _Option::Some(unsafe { _runtime_fmt::codegen::erase(self) }),)*
+                        _ => {
+                            #flat_get
+                            panic!("bad index {}", index)
+                        }
+                    }
                 }
             }
         };
     }
 }
 
-fn build_fields(fields: &[syn::Field]) -> quote::Tokens {
-    let index = 0..fields.len();
-    let ty: Vec<_> = fields.iter().map(|field| &field.ty).collect();
-    let ident: Vec<_> = fields.iter().enumerate().map(|(idx, field)| match field.ident {
-        Some(ref ident) => ident.clone(),
-        None => syn::Ident::from(idx),
-    }).collect();
+/// Find the flattened field which the child at `index` belongs to, and
+/// return the result of `found` for it with `rest` set to the index within
+/// that field.
+fn find_flattened<F>(flattened: &[(syn::Ident, syn::Ty)], own_len: usize, found: F) -> quote::Tokens
+    where F: Fn(&syn::Ident, &syn::Ty) -> quote::Tokens
+{
+    let found = flattened.iter().map(|(member, ty)| {
+        let found = found(member, ty);
+        quote! {
+            if rest < <#ty as _runtime_fmt::FormatArgs>::len() {
+                return #found;
+            }
+            let rest = rest - <#ty as _runtime_fmt::FormatArgs>::len();
+        }
+    });
     quote! {
-        match index {
-            #(
-                #index => _runtime_fmt::codegen::combine::<__F, Self, #ty, _>(
-                    |this| &this.#ident
-                ),
-            )*
-            _ => panic!("bad index {}", index)
+        let rest = index - #own_len;
+        #(#found)*
+    }
+}
+
+/// The options given to the type by `#[format(...)]` attributes.
+#[derive(Default)]
+struct TypeAttrs {
+    methods: Vec<String>,
+    positional: bool,
+}
+
+fn type_attrs(ast: &syn::DeriveInput) -> TypeAttrs {
+    let mut attrs = TypeAttrs::default();
+    for attr in &ast.attrs {
+        let items = match attr.value {
+            syn::MetaItem::List(ref ident, ref items) if ident == "format" => items,
+            _ => continue,
+        };
+        for item in items {
+            match *item {
+                syn::NestedMetaItem::MetaItem(syn::MetaItem::NameValue(ref key, syn::Lit::Str(ref value, _)))
+                    if key == "method" =>
+                {
+                    check_name(value);
+                    if value.contains('-') {
+                        panic!("#[derive(FormatArgs)] cannot call `{}`, which is not a method name", value);
+                    }
+                    attrs.methods.push(value.clone());
+                }
+                syn::NestedMetaItem::MetaItem(syn::MetaItem::Word(ref word)) if word == "positional" => {
+                    attrs.positional = true;
+                }
+                _ => panic!("#[derive(FormatArgs)] does not understand `{}` in #[format(...)] on a type",
+                            quote!(#item)),
+            }
         }
     }
+    attrs
 }
 
-fn build_usize(ast: &syn::DeriveInput, fields: &[syn::Field]) -> quote::Tokens {
-    let self_ = &ast.ident;
-    let (_, ty_generics, where_clause) = ast.generics.split_for_impl();
-
-    // To avoid causing trouble with lifetime elision rules, an explicit
-    // lifetime for the input and output is used.
-    let lifetime = syn::Ident::new("'__as_usize_inner");
-    let mut generics2 = ast.generics.clone();
-    generics2.lifetimes.insert(0, syn::LifetimeDef {
-        attrs: vec![],
-        lifetime: syn::Lifetime { ident: lifetime.clone() },
-        bounds: vec![],
-    });
-    let (impl_generics, _, _) = generics2.split_for_impl();
+/// The options given to a field by `#[format(...)]` attributes.
+#[derive(Default)]
+struct FieldAttrs {
+    rename: Option<String>,
+    aliases: Vec<String>,
+    skip: bool,
+    flatten: bool,
+    /// The formatting traits to format with functions, and those functions.
+    with: Vec<(String, String)>,
+}
+
+/// The formatting traits which `with` may replace.
+const TRAITS: &[&str] = &[
+    "Display", "Debug", "LowerExp", "UpperExp", "Octal", "Pointer", "Binary", "LowerHex", "UpperHex",
+];
 
-    let mut result = quote::Tokens::new();
-    for (idx, field) in fields.iter().enumerate() {
-        let ident = match field.ident {
-            Some(ref ident) => ident.clone(),
-            None => syn::Ident::from(idx),
+fn field_attrs(field: &syn::Field) -> FieldAttrs {
+    let mut attrs = FieldAttrs::default();
+    for attr in &field.attrs {
+        let items = match attr.value {
+            syn::MetaItem::List(ref ident, ref items) if ident == "format" => items,
+            _ => continue,
         };
-        let ty = &field.ty;
-        result.append(quote! {
-            #idx => {
-                fn inner #impl_generics (this: &#lifetime #self_ #ty_generics)
-                    -> &#lifetime #ty
-                    #where_clause { &this.#ident }
-                _runtime_fmt::codegen::as_usize(inner)
-            },
+        for item in items {
+            match *item {
+                syn::NestedMetaItem::MetaItem(syn::MetaItem::Word(ref word)) if word == "skip" => {
+                    attrs.skip = true;
+                }
+                syn::NestedMetaItem::MetaItem(syn::MetaItem::Word(ref word)) if word == "flatten" => {
+                    attrs.flatten = true;
+                }
+                syn::NestedMetaItem::MetaItem(syn::MetaItem::NameValue(ref key, syn::Lit::Str(ref value, _)))
+                    if key == "rename" || key == "alias" =>
+                {
+                    check_name(value);
+                    if key == "rename" {
+                        attrs.rename = Some(value.clone());
+                    } else {
+                        attrs.aliases.push(value.clone());
+                    }
+                }
+                syn::NestedMetaItem::MetaItem(syn::MetaItem::NameValue(ref key, syn::Lit::Str(ref value, _)))
+                    if key == "with" =>
+                {
+                    attrs.add_with("Display", value);
+                }
+                syn::NestedMetaItem::MetaItem(syn::MetaItem::List(ref key, ref items)) if key == "with" => {
+                    for item in items {
+                        match *item {
+                            syn::NestedMetaItem::MetaItem(
+                                syn::MetaItem::NameValue(ref name, syn::Lit::Str(ref value, _))
+                            ) => attrs.add_with(name.as_ref(), value),
+                            _ => panic!("#[derive(FormatArgs)] expected `Trait = \"path::to::function\"` \
+                                         in #[format(with(...))], not `{}`", quote!(#item)),
+                        }
+                    }
+                }
+                _ => panic!("#[derive(FormatArgs)] does not understand `{}` in #[format(...)]",
+                            quote!(#item)),
+            }
+        }
+    }
+    if attrs.flatten && (attrs.rename.is_some() || !attrs.aliases.is_empty()) {
+        panic!("#[derive(FormatArgs)] cannot rename or alias a flattened field");
+    }
+    if attrs.flatten && !attrs.with.is_empty() {
+        panic!("#[derive(FormatArgs)] cannot format a flattened field with a function");
+    }
+    attrs
+}
+
+impl FieldAttrs {
+    fn add_with(&mut self, name: &str, path: &str) {
+        if !TRAITS.contains(&name) {
+            panic!("#[derive(FormatArgs)] cannot format with a function for `{}`, which is not a \
+                    formatting trait", name);
+        }
+        if let Err(err) = syn::parse_path(path) {
+            panic!("#[derive(FormatArgs)] cannot parse `{}` as a path: {}", path, err);
+        }
+        if self.with.iter().any(|(other, _)| other == name) {
+            panic!("#[derive(FormatArgs)] found more than one function for `{}` on one field", name);
+        }
+        self.with.push((name.to_owned(), path.to_owned()));
+    }
+}
+
+/// Names must be words, or words joined by single hyphens, as only those can
+/// be written in format strings.
+fn check_name(name: &str) {
+    let valid = name.starts_with(char::is_alphabetic) && name.split('-').all(|word| {
+        !word.is_empty() && word.chars().all(|c| c.is_alphanumeric() || c == '_')
+    });
+    if !valid {
+        panic!("#[derive(FormatArgs)] cannot use `{}` as a name, as format strings can only \
+                refer to names which begin with a letter followed by letters, digits, and \
+                underscores, with single hyphens between them", name);
+    }
+}
+
+/// A child of the derived type, addressable by index or by name.
+struct Child {
+    /// Whether the child is addressed by index, as well as by any names.
+    positional: bool,
+    /// The names of the child, its primary name first.
+    names: Vec<String>,
+    ty: syn::Ty,
+    /// The formatting traits to format with functions, and those functions.
+    with: Vec<(String, String)>,
+    /// The variants which have this child and the field of each which it
+    /// is, or the field alone with no variant for a struct.
+    variants: Vec<(Option<syn::Ident>, syn::Ident)>,
+}
+
+impl Child {
+    fn new(field: &syn::Field, attrs: FieldAttrs, variant: Option<syn::Ident>, member: syn::Ident)
+        -> Child
+    {
+        let primary = attrs.rename.or_else(|| field.ident.as_ref().map(ToString::to_string));
+        Child {
+            positional: field.ident.is_none(),
+            names: primary.into_iter().chain(attrs.aliases).collect(),
+            ty: field.ty.clone(),
+            with: attrs.with,
+            variants: vec![(variant, member)],
+        }
+    }
+
+    /// An expression evaluating to the `Traits` of the child.
+    fn traits(&self) -> quote::Tokens {
+        let ty = &self.ty;
+        let with = self.with.iter().map(|(name, path)| {
+            let path = syn::parse_path(path).unwrap();
+            quote! { .using(#name, #path) }
         });
+        quote! { _runtime_fmt::__rt_traits!(type #ty) #(#with)* }
     }
 
-    quote! {
-        match index {
-            #result
-            _ => panic!("bad index {}", index)
+    /// An expression evaluating to the erased child of `self`, if present.
+    fn get(&self, ident: &syn::Ident) -> quote::Tokens {
+        if let (None, ref member) = self.variants[0] {
+            return quote! { _Option::Some(unsafe { _runtime_fmt::codegen::erase(&self.#member) }) };
+        }
+        let variant = self.variants.iter().map(|(variant, _)| variant);
+        let member = self.variants.iter().map(|(_, member)| member);
+        let ident = ::std::iter::repeat(ident);
+        quote! {
+            match *self {
+                #(#ident::#variant { #member: ref value, .. } => {
+                    _Option::Some(unsafe { _runtime_fmt::codegen::erase(value) })
+                })*
+                _ => _Option::None,
+            }
+        }
+    }
+}
+
+/// The children of a struct, and its flattened fields.
+fn struct_children(variant: &syn::VariantData) -> (Vec<Child>, Vec<(syn::Ident, syn::Ty)>) {
+    let fields = variant.fields();
+    let mut children = Vec::new();
+    let mut flattened = Vec::new();
+    for (field, member) in fields.iter().zip(field_idents(fields)) {
+        let attrs = field_attrs(field);
+        if attrs.skip {
+            continue;
+        } else if attrs.flatten {
+            flattened.push((member, field.ty.clone()));
+        } else {
+            children.push(Child::new(field, attrs, None, member));
+        }
+    }
+    (children, flattened)
+}
+
+/// The children of an enum are the union of the fields of its variants.
+/// Fields of the same name, or at the same index, in different variants are
+/// the same child, and must have the same type.
+fn enum_children(ident: &syn::Ident, variants: &[syn::Variant]) -> Vec<Child> {
+    let mut children: Vec<Child> = Vec::new();
+    for variant in variants {
+        let fields = variant.data.fields();
+        let mut position = 0;
+        for (field, member) in fields.iter().zip(field_idents(fields)) {
+            let attrs = field_attrs(field);
+            if attrs.skip {
+                continue;
+            } else if attrs.flatten {
+                panic!("#[derive(FormatArgs)] does not support flattening the fields of enums");
+            }
+            let child = Child::new(field, attrs, Some(variant.ident.clone()), member);
+            // Skipped fields are not counted when numbering positional ones.
+            let existing = if child.positional {
+                position += 1;
+                children.iter().enumerate()
+                    .filter(|&(_, c)| c.positional)
+                    .map(|(i, _)| i)
+                    .nth(position - 1)
+            } else {
+                children.iter().position(|c| !c.positional && c.names[0] == child.names[0])
+            };
+            match existing {
+                Some(i) => {
+                    let existing = &mut children[i];
+                    if existing.ty != child.ty {
+                        let (first, second) = (&existing.ty, &child.ty);
+                        panic!("#[derive(FormatArgs)] requires field `{}` of `{}` to have the \
+                                same type in every variant, but it is `{}` in `{}` and `{}` in \
+                                `{}`", child.variants[0].1, ident, quote!(#first),
+                               existing.variants[0].0.as_ref().unwrap(), quote!(#second),
+                               variant.ident);
+                    }
+                    if existing.with != child.with {
+                        panic!("#[derive(FormatArgs)] requires field `{}` of `{}` to be formatted \
+                                with the same functions in every variant", child.variants[0].1, ident);
+                    }
+                    for name in child.names {
+                        if !existing.names.contains(&name) {
+                            existing.names.push(name);
+                        }
+                    }
+                    existing.variants.extend(child.variants);
+                }
+                None => children.push(child),
+            }
         }
     }
+    children
+}
+
+fn field_idents(fields: &[syn::Field]) -> Vec<syn::Ident> {
+    fields.iter().enumerate().map(|(idx, field)| match field.ident {
+        Some(ref ident) => ident.clone(),
+        None => syn::Ident::from(idx),
+    }).collect()
 }
@@ -0,0 +1,399 @@
+//! The syntax tree of a parsed format string.
+//!
+//! `Template::parse` breaks a format string into literal text and
+//! placeholders, without reference to any arguments. Each part records the
+//! span of the format string it came from, so that tools can inspect or
+//! report on templates without reimplementing the grammar:
+//!
+//! ```
+//! use runtime_fmt::ast::{Template, Piece, Position};
+//!
+//! let template = Template::parse("Hello, {name:>8}!").unwrap();
+//! match template.pieces[1] {
+//!     Piece::Placeholder(ref placeholder) => {
+//!         assert_eq!(placeholder.position, Position::Named("name".to_owned()));
+//!         assert_eq!(placeholder.format.width, runtime_fmt::ast::Count::Is(8));
+//!     }
+//!     _ => unreachable!(),
+//! }
+//! ```
+//!
+//! A `Template` displays as its format string in canonical form, so that
+//! format strings which are written differently but behave identically can
+//! be compared:
+//!
+//! ```
+//! use runtime_fmt::ast::Template;
+//!
+//! let canonical = |spec| Template::parse(spec).unwrap().to_string();
+//! assert_eq!(canonical("{} {:-<5}"), "{0} {1:-<5}");
+//! assert_eq!(canonical("{0} {1: <5}"), "{0} {1:<5}");
+//! ```
+use std::fmt::{self, Write};
+
+use fmt_macros as p;
+use {Span, SyntaxError};
+
+/// A parsed format string.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Template {
+    /// The literal text and placeholders of the format string, in order.
+    /// Adjacent literal text is always merged into a single piece.
+    pub pieces: Vec<Piece>,
+}
+
+/// A portion of a format string.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[allow(clippy::large_enum_variant)]
+pub enum Piece {
+    /// Literal text, with any escaped braces unescaped.
+    Literal(Literal),
+    /// A placeholder for an argument, such as `{0:>8}`.
+    Placeholder(Placeholder),
+}
+
+/// Literal text in a format string.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Literal {
+    /// The text to emit, with `{{` and `}}` replaced by single braces.
+    pub text: String,
+    /// The span of the text in the format string, including escapes.
+    pub span: Span,
+}
+
+/// A placeholder for an argument, including its braces.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Placeholder {
+    /// The argument to format.
+    pub position: Position,
+    /// The span of the position, which is empty if it was implicit.
+    pub position_span: Span,
+    /// The path from the argument to the value to format, as in
+    /// `{user.name}` or `{items[0]}`.
+    pub path: Vec<Segment>,
+    /// The span of the whole placeholder, including its braces.
+    pub span: Span,
+    /// How to format the argument.
+    pub format: FormatSpec,
+}
+
+/// The argument a placeholder refers to.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Position {
+    /// The next positional argument, as in `{}`, at the given index.
+    Next(usize),
+    /// An explicit positional argument, as in `{0}`.
+    Index(usize),
+    /// A named argument, as in `{name}`.
+    Named(String),
+}
+
+/// A step along the path from an argument to the value to format.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Segment {
+    /// What the step accesses.
+    pub access: Access,
+    /// The span of the step, including its `.` or brackets.
+    pub span: Span,
+}
+
+/// How a step along a path is accessed.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Access {
+    /// A field, by name or by index, as in `.name` or `.0`.
+    Field(String),
+    /// An element of a sequence, counting from the end if negative, as in
+    /// `[0]` or `[-1]`.
+    Index(isize),
+    /// An element of a map, by key, as in `["key"]`.
+    Key(String),
+}
+
+/// The options of a placeholder, following the `:`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct FormatSpec {
+    /// The fill character, if any.
+    pub fill: Option<char>,
+    /// The alignment within the width.
+    pub align: Alignment,
+    /// The sign flag, if any.
+    pub sign: Option<Sign>,
+    /// Whether the alternate flag `#` was given.
+    pub alternate: bool,
+    /// Whether the zero-padding flag `0` was given.
+    pub zero_pad: bool,
+    /// The minimum width.
+    pub width: Count,
+    /// The span of the width, if any.
+    pub width_span: Option<Span>,
+    /// The precision.
+    pub precision: Count,
+    /// The span of the precision including its `.`, if any.
+    pub precision_span: Option<Span>,
+    /// The formatting trait, such as `"?"` or `"x"`, or `""` for `Display`.
+    pub ty: String,
+    /// The span of the formatting trait, if any.
+    pub ty_span: Option<Span>,
+    /// The text to format in place of an absent value, as in `{name|none}`,
+    /// in which case the other options are the defaults.
+    pub fallback: Option<String>,
+    /// The span of the fallback including its `|`, if any.
+    pub fallback_span: Option<Span>,
+}
+
+/// The alignment of a placeholder.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Alignment {
+    /// `<`
+    Left,
+    /// `>`
+    Right,
+    /// `^`
+    Center,
+    /// No alignment was given.
+    Unknown,
+}
+
+/// The sign flag of a placeholder.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Sign {
+    /// `+`
+    Plus,
+    /// `-`
+    Minus,
+}
+
+/// A width or precision.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Count {
+    /// A literal count, as in `8`.
+    Is(usize),
+    /// The value of a named argument, as in `width$`.
+    Name(String),
+    /// The value of a positional argument, as in `1$`.
+    Param(usize),
+    /// The value of the next positional argument, as in `.*`, at the given
+    /// index.
+    Next(usize),
+    /// No count was given.
+    Implied,
+}
+
+impl Template {
+    /// Parse a format string, returning every syntax error it contains if it
+    /// is invalid.
+    pub fn parse(spec: &str) -> Result<Template, Vec<SyntaxError>> {
+        let mut parser = p::Parser::new(spec);
+        let mut pieces = Vec::new();
+        for piece in &mut parser {
+            let piece = match piece {
+                p::String(text) => {
+                    // Literal text only begins with a brace if it is escaped,
+                    // in which case the first of the pair is just before it.
+                    let mut start = text.as_ptr() as usize - spec.as_ptr() as usize;
+                    let end = start + text.len();
+                    if text.starts_with('{') || text.starts_with('}') {
+                        start -= 1;
+                    }
+                    if let Some(&mut Piece::Literal(ref mut prev)) = pieces.last_mut() {
+                        prev.text.push_str(text);
+                        prev.span.end = end;
+                        continue;
+                    }
+                    Piece::Literal(Literal {
+                        text: text.to_owned(),
+                        span: Span { start: start, end: end },
+                    })
+                }
+                p::NextArgument(arg) => Piece::Placeholder(Placeholder::from_inner(&arg)),
+            };
+            pieces.push(piece);
+        }
+        if parser.errors.is_empty() {
+            Ok(Template { pieces: pieces })
+        } else {
+            Err(parser.errors.into_iter().map(SyntaxError::from_inner).collect())
+        }
+    }
+}
+
+impl Placeholder {
+    fn from_inner(arg: &p::Argument) -> Placeholder {
+        let position_span = Span::from_inner(arg.position_span);
+        let position = match arg.position {
+            p::ArgumentIs(i) if position_span.start == position_span.end => Position::Next(i),
+            p::ArgumentIs(i) => Position::Index(i),
+            p::ArgumentNamed(name) => Position::Named(name.to_owned()),
+        };
+        let format = &arg.format;
+        let flag = |flag: p::Flag| format.flags & (1 << flag as u32) != 0;
+        let precision_span = format.precision_span.map(Span::from_inner);
+        let precision = match format.precision {
+            // `.*` is the only way to write a precision of two characters
+            // which refers to an argument.
+            p::CountIsParam(i) if precision_span.is_some_and(|s| s.end - s.start == 2) =>
+                Count::Next(i),
+            ref other => Count::from_inner(other),
+        };
+        Placeholder {
+            position: position,
+            position_span: position_span,
+            path: arg.path.iter().map(Segment::from_inner).collect(),
+            span: Span::from_inner(arg.span),
+            format: FormatSpec {
+                fill: format.fill,
+                align: match format.align {
+                    p::AlignLeft => Alignment::Left,
+                    p::AlignRight => Alignment::Right,
+                    p::AlignCenter => Alignment::Center,
+                    p::AlignUnknown => Alignment::Unknown,
+                },
+                sign: if flag(p::FlagSignPlus) {
+                    Some(Sign::Plus)
+                } else if flag(p::FlagSignMinus) {
+                    Some(Sign::Minus)
+                } else {
+                    None
+                },
+                alternate: flag(p::FlagAlternate),
+                zero_pad: flag(p::FlagSignAwareZeroPad),
+                width: Count::from_inner(&format.width),
+                width_span: format.width_span.map(Span::from_inner),
+                precision: precision,
+                precision_span: precision_span,
+                ty: format.ty.to_owned(),
+                ty_span: format.ty_span.map(Span::from_inner),
+                fallback: format.fallback.map(str::to_owned),
+                fallback_span: format.fallback_span.map(Span::from_inner),
+            },
+        }
+    }
+}
+
+impl Segment {
+    fn from_inner(segment: &p::Segment) -> Segment {
+        Segment {
+            access: Access::from_inner(&segment.access),
+            span: Span::from_inner(segment.span),
+        }
+    }
+}
+
+impl Count {
+    fn from_inner(count: &p::Count) -> Count {
+        match *count {
+            p::CountIs(n) => Count::Is(n),
+            p::CountIsName(name) => Count::Name(name.to_owned()),
+            p::CountIsParam(i) => Count::Param(i),
+            p::CountImplied => Count::Implied,
+        }
+    }
+}
+
+/// Writes the format string in canonical form: positions are explicit, a
+/// space fill and the `-` flag (which have no effect) are omitted, and an
+/// empty format spec is omitted along with its `:`.
+impl fmt::Display for Template {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        for piece in &self.pieces {
+            fmt::Display::fmt(piece, fmt)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Piece {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Piece::Literal(ref literal) => fmt::Display::fmt(literal, fmt),
+            Piece::Placeholder(ref placeholder) => fmt::Display::fmt(placeholder, fmt),
+        }
+    }
+}
+
+/// Writes the text with its braces escaped.
+impl fmt::Display for Literal {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        for c in self.text.chars() {
+            if c == '{' || c == '}' {
+                fmt.write_char(c)?;
+            }
+            fmt.write_char(c)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Placeholder {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_char('{')?;
+        match self.position {
+            Position::Next(i) | Position::Index(i) => write!(fmt, "{}", i)?,
+            Position::Named(ref name) => fmt.write_str(name)?,
+        }
+        for segment in &self.path {
+            match segment.access {
+                Access::Field(ref field) => write!(fmt, ".{}", field)?,
+                Access::Index(index) => write!(fmt, "[{}]", index)?,
+                Access::Key(ref key) => write!(fmt, "[\"{}\"]", key)?,
+            }
+        }
+        let spec = self.format.to_string();
+        if self.format.fallback.is_none() && !spec.is_empty() {
+            fmt.write_char(':')?;
+        }
+        fmt.write_str(&spec)?;
+        fmt.write_char('}')
+    }
+}
+
+/// Writes the options in canonical form, without the leading `:`, or the
+/// fallback with its leading `|`, followed by a `:` and any options.
+impl fmt::Display for FormatSpec {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(ref fallback) = self.fallback {
+            write!(fmt, "|{}", fallback)?;
+            let options = FormatSpec { fallback: None, ..self.clone() }.to_string();
+            if !options.is_empty() {
+                write!(fmt, ":{}", options)?;
+            }
+            return Ok(());
+        }
+        let align = match self.align {
+            Alignment::Left => "<",
+            Alignment::Right => ">",
+            Alignment::Center => "^",
+            Alignment::Unknown => "",
+        };
+        if let Some(fill) = self.fill {
+            if fill != ' ' && !align.is_empty() {
+                fmt.write_char(fill)?;
+            }
+        }
+        fmt.write_str(align)?;
+        if self.sign == Some(Sign::Plus) {
+            fmt.write_char('+')?;
+        }
+        if self.alternate {
+            fmt.write_char('#')?;
+        }
+        if self.zero_pad {
+            fmt.write_char('0')?;
+        }
+        write_count(fmt, &self.width)?;
+        if self.precision != Count::Implied {
+            fmt.write_char('.')?;
+            write_count(fmt, &self.precision)?;
+        }
+        fmt.write_str(&self.ty)
+    }
+}
+
+fn write_count(fmt: &mut fmt::Formatter, count: &Count) -> fmt::Result {
+    match *count {
+        Count::Is(n) => write!(fmt, "{}", n),
+        Count::Name(ref name) => write!(fmt, "{}$", name),
+        Count::Param(i) | Count::Next(i) => write!(fmt, "{}$", i),
+        Count::Implied => Ok(()),
+    }
+}
@@ -0,0 +1,171 @@
+//! `FormatArgs` for tuples, arrays, vectors and slices, whose children are
+//! their elements by index.
+use codegen::{child, erase, Child, FormatArgs, Formattable, Opaque};
+
+macro_rules! tuples {
+    ($(($len:expr; $($idx:tt $name:ident)*))*) => {$(
+        unsafe impl<$($name: Formattable),*> FormatArgs for ($($name,)*) {
+            #[inline]
+            fn validate_name(_: &str) -> Option<usize> {
+                None
+            }
+
+            #[inline]
+            fn validate_index(index: usize) -> bool {
+                index < $len
+            }
+
+            #[inline]
+            fn len() -> usize {
+                $len
+            }
+
+            fn get_child(index: usize) -> Child {
+                match index {
+                    $($idx => child($name::traits()),)*
+                    _ => panic!("bad index {}", index),
+                }
+            }
+
+            fn get(&self, index: usize) -> Option<&Opaque> {
+                match index {
+                    $($idx => Some(unsafe { erase(&self.$idx) }),)*
+                    _ => panic!("bad index {}", index),
+                }
+            }
+        }
+    )*}
+}
+
+tuples! {
+    (1; 0 A)
+    (2; 0 A 1 B)
+    (3; 0 A 1 B 2 C)
+    (4; 0 A 1 B 2 C 3 D)
+    (5; 0 A 1 B 2 C 3 D 4 E)
+    (6; 0 A 1 B 2 C 3 D 4 E 5 F)
+    (7; 0 A 1 B 2 C 3 D 4 E 5 F 6 G)
+    (8; 0 A 1 B 2 C 3 D 4 E 5 F 6 G 7 H)
+    (9; 0 A 1 B 2 C 3 D 4 E 5 F 6 G 7 H 8 I)
+    (10; 0 A 1 B 2 C 3 D 4 E 5 F 6 G 7 H 8 I 9 J)
+    (11; 0 A 1 B 2 C 3 D 4 E 5 F 6 G 7 H 8 I 9 J 10 K)
+    (12; 0 A 1 B 2 C 3 D 4 E 5 F 6 G 7 H 8 I 9 J 10 K 11 L)
+}
+
+unsafe impl<T: Formattable, const N: usize> FormatArgs for [T; N] {
+    #[inline]
+    fn validate_name(_: &str) -> Option<usize> {
+        None
+    }
+
+    #[inline]
+    fn validate_index(index: usize) -> bool {
+        index < N
+    }
+
+    #[inline]
+    fn len() -> usize {
+        N
+    }
+
+    #[inline]
+    fn get_child(_: usize) -> Child {
+        child(T::traits())
+    }
+
+    #[inline]
+    fn get(&self, index: usize) -> Option<&Opaque> {
+        Some(unsafe { erase(&self[index]) })
+    }
+}
+
+/// The length of a slice is not known until it is formatted, so any index
+/// is accepted when preparing, those past the end are not present, and none
+/// are reported unused by strict parsing.
+unsafe impl<T: Formattable> FormatArgs for [T] {
+    #[inline]
+    fn validate_name(_: &str) -> Option<usize> {
+        None
+    }
+
+    #[inline]
+    fn validate_index(_: usize) -> bool {
+        true
+    }
+
+    #[inline]
+    fn len() -> usize {
+        0
+    }
+
+    #[inline]
+    fn get_child(_: usize) -> Child {
+        child(T::traits())
+    }
+
+    #[inline]
+    fn get(&self, index: usize) -> Option<&Opaque> {
+        <[T]>::get(self, index).map(|value| unsafe { erase(value) })
+    }
+}
+
+unsafe impl<T: Formattable> FormatArgs for Vec<T> {
+    #[inline]
+    fn validate_name(_: &str) -> Option<usize> {
+        None
+    }
+
+    #[inline]
+    fn validate_index(_: usize) -> bool {
+        true
+    }
+
+    #[inline]
+    fn len() -> usize {
+        0
+    }
+
+    #[inline]
+    fn get_child(_: usize) -> Child {
+        child(T::traits())
+    }
+
+    #[inline]
+    fn get(&self, index: usize) -> Option<&Opaque> {
+        <[T]>::get(self, index).map(|value| unsafe { erase(value) })
+    }
+}
+
+#[cfg(feature = "nightly")]
+impl<T> Formattable for T {
+    #[inline]
+    default fn traits() -> ::Traits<T> {
+        ::Traits::detect()
+    }
+}
+
+macro_rules! formattable {
+    ($($t:ty)*) => {$(
+        #[cfg(not(feature = "nightly"))]
+        impl Formattable for $t {
+            #[inline]
+            fn traits() -> ::Traits<$t> {
+                ::__rt_traits!(type $t)
+            }
+        }
+    )*}
+}
+
+formattable! {
+    i8 i16 i32 i64 i128 isize
+    u8 u16 u32 u64 u128 usize
+    f32 f64 bool char String
+}
+
+#[cfg(not(feature = "nightly"))]
+impl<'a> Formattable for &'a str {
+    #[inline]
+    fn traits() -> ::Traits<&'a str> {
+        ::__rt_traits!(type &'a str)
+    }
+}
@@ -1,172 +1,155 @@
 //! Support for the codegen module.
 #![doc(hidden)]
 
-use std::mem::{size_of, zeroed};
-use std::fmt::*;
+use std::borrow::Cow;
 
-/// Implementors correspond to formatting traits which may apply to values.
-pub trait FormatTrait {
-    /// Return whether this format trait is applicable to a type.
-    #[inline]
-    fn allowed<T>() -> bool;
-    /// Format a value of the given trait using this format trait.
-    /// Must panic if `allowed::<T>()` is false.
-    #[inline]
-    fn perform<T>(t: &T, f: &mut Formatter) -> Result;
-}
+use Traits;
 
-// Abuse specialization to provide the `FormatTrait` impl for the actual
-// format traits without requiring HKT or other deep chicanery.
-trait Specialized<T> {
-    #[inline]
-    fn allowed() -> bool;
-    #[inline]
-    fn perform(t: &T, f: &mut Formatter) -> Result;
-}
+pub use erase::{erase, call_computed, Call, Compute, Opaque};
 
-macro_rules! impl_format_trait {
-    ($($name:ident,)*) => {
-        $(
-            impl<T> Specialized<T> for dyn $name {
-                #[inline]
-                default fn allowed() -> bool { false }
-                #[inline]
-                default fn perform(_: &T, _: &mut Formatter) -> Result {
-                    panic!()
-                }
-            }
-
-            impl<T: $name> Specialized<T> for dyn $name {
-                #[inline]
-                fn allowed() -> bool { true }
-                #[inline]
-                fn perform(t: &T, f: &mut Formatter) -> Result {
-                    t.fmt(f)
-                }
-            }
-
-            impl FormatTrait for dyn $name {
-                #[inline]
-                fn allowed<T>() -> bool { <Self as Specialized<T>>::allowed() }
-                #[inline]
-                fn perform<T>(t: &T, f: &mut Formatter) -> Result {
-                    <Self as Specialized<T>>::perform(t, f)
-                }
-            }
-        )*
-    }
-}
+pub use erase::{Probe, Count, Classified, Nested};
+#[cfg(feature = "nightly")]
+pub use erase::{detect_of, detect_returned};
+pub use erase::{Has, Lacks, HasCount, LacksCount, HasKind, LacksKind, HasFields, LacksFields};
+pub use index::{Sequenced, Keyed, Optional, HasSequence, LacksSequence, HasMap, LacksMap, HasOption, LacksOption};
+pub use std::fmt::{Display, Debug, LowerExp, UpperExp, Octal, Pointer, Binary, LowerHex, UpperHex};
 
-impl_format_trait! {
-    Display, Debug, LowerExp, UpperExp, Octal, Pointer, Binary, LowerHex,
-    UpperHex,
+/// A child of a formattable type: the formatting traits the child supports,
+/// including whether it may be used as a count, and the method which
+/// computes it, if it is not a field.
+pub struct Child {
+    pub traits: Traits<Opaque>,
+    pub compute: Option<Compute>,
 }
 
+/// Describe a child of type `B` by its formatting traits.
 #[inline]
-fn get_formatter<T, F: FormatTrait + ?Sized>()
-    -> Option<impl Fn(&T, &mut Formatter) -> Result>
-{
-    if F::allowed::<T>() {
-        Some(F::perform::<T>)
-    } else {
-        None
+pub fn child<B>(traits: Traits<B>) -> Child {
+    Child {
+        traits: traits.erase(),
+        compute: None,
     }
 }
 
-#[inline]
-// The combined function which will be returned by `make_combined`.
-fn combined<A, B, LHS, RHS>(a: &A, f: &mut Formatter) -> Result
-    where LHS: Fn(&A) -> &B, RHS: Fn(&B, &mut Formatter) -> Result
-{
-    let lhs = unsafe { zeroed::<LHS>() };
-    let rhs = unsafe { zeroed::<RHS>() };
-    rhs(lhs(a), f)
-}
-
-// Local type alias for the formatting function pointer type.
-type FormatFn<T> = fn(&T, &mut Formatter) -> Result;
-
-// Accepts dummy arguments to allow type parameter inference, and returns
-// `combined` instantiated with those arguments.
-#[inline]
-fn make_combined<A, B, LHS, RHS>(_: LHS, _: RHS) -> FormatFn<A>
-    where LHS: Fn(&A) -> &B, RHS: Fn(&B, &mut Formatter) -> Result
-{
-    // check that both function
-    assert!(size_of::<LHS>() == 0,
-        "Mapper from parent to child must be zero-sized, instead size was {}",
-        size_of::<LHS>());
-    assert!(size_of::<RHS>() == 0,
-        "Formatting function must be zero-sized, instead size was {}",
-        size_of::<RHS>());
-    combined::<A, B, LHS, RHS>
-}
-
-/// Combine a function from `&A` to `&B` and a formatting trait applicable to
-/// `B` and return a function pointer which will convert a `&A` to `&B` and
-/// then format it with the given trait.
+/// Describe a child of type `B` computed by a method of its parent, which
+/// `get` should return in place of the child, and which `call` passes to
+/// `call_computed` along with the method. Such a child can be formatted, but
+/// paths cannot reach into it, nor can it be used as a count.
 ///
-/// Returns `None` if the formatting trait is not applicable to `B`.
-/// Panics if `func` is not zero-sized.
-#[inline]
-pub fn combine<F, A, B, Func>(func: Func)
-    -> Option<FormatFn<A>>
-    where F: FormatTrait + ?Sized, Func: Fn(&A) -> &B
-{
-    // Combines `get_formatter` and `make_combined` in one.
-    get_formatter::<B, F>().map(|r| make_combined(func, r))
-}
-
-// Specialization abuse to select only functions which return `&usize`.
-trait SpecUsize {
-    #[inline]
-    fn convert<T>(f: fn(&T) -> &Self) -> Option<fn(&T) -> &usize>;
-}
-
-impl<U> SpecUsize for U {
-    #[inline]
-    default fn convert<T>(_: fn(&T) -> &Self) -> Option<fn(&T) -> &usize> { None }
-}
-
-impl SpecUsize for usize {
-    #[inline]
-    fn convert<T>(f: fn(&T) -> &usize) -> Option<fn(&T) -> &usize> { Some(f) }
-}
-
-/// Attempt to convert a function from `&A` to `&B` to a function from `&A`
-/// to `&usize`. Returns `Some` only when `B` is `usize`.
+/// # Safety
+///
+/// `call` is given the parent and a formatting function taken from
+/// `traits`, so it must format a value of type `B` computed from the
+/// parent, as `call_computed` does for a method returning `B`.
 #[inline]
-pub fn as_usize<A, B>(f: fn(&A) -> &B) -> Option<fn(&A) -> &usize> {
-    <B as SpecUsize>::convert::<A>(f)
+pub unsafe fn computed<B>(traits: Traits<B>, call: Call) -> Child {
+    Child {
+        traits: traits.erase().without_paths(),
+        compute: Some(Compute::new(call)),
+    }
 }
 
 /// A trait for types against which formatting specifiers may be pre-checked.
 ///
 /// Implementations may be generated automatically using `runtime-fmt-derive`
 /// and `#[derive(FormatArgs)]`.
-pub trait FormatArgs {
+///
+/// # Safety
+///
+/// Values are formatted through the `Traits` which `get_child` describes,
+/// without checking their types. For each index, `get` must return either
+/// `None` or a reference, erased by `erase`, to a value of exactly the type
+/// whose `Traits` `get_child` returns, or to `self` for a child computed by
+/// a method.
+pub unsafe trait FormatArgs {
     /// Find the index within this type corresponding to the provided name.
     ///
     /// If this function returns `Some`, `get_child` with the returned index
     /// must not panic.
     fn validate_name(name: &str) -> Option<usize>;
 
+    /// List the names which `validate_name` accepts, from which suggestions
+    /// are drawn when an unknown name is used. The name of each child should
+    /// be listed at its index, with an empty string for unnamed children.
+    fn names() -> Cow<'static, [&'static str]> {
+        Cow::Borrowed(&[])
+    }
+
     /// Validate that a given index is within range for this type.
     ///
     /// If this function returns `true`, `get_child` with the given index must
     /// not panic.
     fn validate_index(index: usize) -> bool;
 
-    /// Return the formatter function for the given format trait, accepting
-    /// `&Self` and using the given format trait on the value at that index.
+    /// The number of children this type has, each of which is expected to be
+    /// referred to by a format string prepared strictly.
     ///
-    /// Returns `None` if the given format trait cannot format the child at
-    /// that index. Panics if the index is invalid.
-    fn get_child<F: FormatTrait + ?Sized>(index: usize) -> Option<FormatFn<Self>>;
+    /// Children at indices past this are never reported unused, and are
+    /// reported out of range rather than missing when absent from a value, so
+    /// a type whose number of children is not known without a value, such as
+    /// a slice, reports zero.
+    fn len() -> usize;
+
+    /// Return the formatting traits the child at the given index supports,
+    /// and the method which computes it, if any.
+    ///
+    /// Panics if the index is invalid.
+    fn get_child(index: usize) -> Child;
 
-    /// Return the value at the given index interpreted as a `usize`.
+    /// Return a reference to the child at the given index, with its type
+    /// erased, or `None` if it is not present in this value.
+    ///
+    /// Every child of a struct is always present, but the children of an
+    /// enum are the union of the fields of its variants, of which only those
+    /// of the current variant are present. The reference must be to a value
+    /// of the type `get_child` describes, or to `self` for a child computed
+    /// by a method.
     ///
-    /// Returns `None` if the child at the given index cannot be interpreted
-    /// as a `usize`. Panics if the index is invalid.
-    fn as_usize(index: usize) -> Option<fn(&Self) -> &usize>;
+    /// Panics if the index is invalid.
+    fn get(&self, index: usize) -> Option<&Opaque>;
+}
+
+/// A type whose formatting traits are known without a value of it, as the
+/// elements of the tuples, arrays, vectors and slices which implement
+/// `FormatArgs` must be.
+///
+/// With the `nightly` feature enabled, every type implements this through
+/// specialization. Otherwise, it is implemented for the primitive types,
+/// `String` and `&str`, and may be implemented for other types by building
+/// their `Traits` by hand.
+pub trait Formattable: Sized {
+    /// The formatting traits the type supports.
+    fn traits() -> Traits<Self>;
+}
+
+unsafe impl<T: FormatArgs + ?Sized> FormatArgs for &T {
+    #[inline]
+    fn validate_name(name: &str) -> Option<usize> {
+        T::validate_name(name)
+    }
+
+    #[inline]
+    fn names() -> Cow<'static, [&'static str]> {
+        T::names()
+    }
+
+    #[inline]
+    fn validate_index(index: usize) -> bool {
+        T::validate_index(index)
+    }
+
+    #[inline]
+    fn len() -> usize {
+        T::len()
+    }
+
+    #[inline]
+    fn get_child(index: usize) -> Child {
+        T::get_child(index)
+    }
+
+    #[inline]
+    fn get(&self, index: usize) -> Option<&Opaque> {
+        (**self).get(index)
+    }
 }
@@ -0,0 +1,114 @@
+//! Comparison of the arguments used by two format strings.
+use std::fmt;
+
+use ast::Access;
+use {Role, Usage};
+
+/// A way in which a format string uses its arguments differently from
+/// another it is meant to be compatible with, such as a translation and its
+/// source.
+///
+/// Each difference carries the usage it concerns, whose span refers to the
+/// source for `Missing` and `MissingCount`, and to the translation otherwise.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Difference {
+    /// The source uses an argument which the translation never uses.
+    Missing(Usage),
+    /// The translation uses an argument which the source never uses.
+    Extra(Usage),
+    /// The translation uses an argument in a way the source never does:
+    /// through another formatting trait, or as a count.
+    NewRole(Usage),
+    /// The source uses an argument as a width or precision, which the
+    /// translation never does.
+    MissingCount(Usage),
+}
+
+impl Difference {
+    /// The usage this difference concerns.
+    pub fn usage(&self) -> &Usage {
+        match *self {
+            Difference::Missing(ref usage) |
+            Difference::Extra(ref usage) |
+            Difference::NewRole(ref usage) |
+            Difference::MissingCount(ref usage) => usage,
+        }
+    }
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let usage = self.usage();
+        let mut argument = match usage.name {
+            Some(name) => name.to_owned(),
+            None => usage.idx.to_string(),
+        };
+        for access in &usage.path {
+            match *access {
+                Access::Field(ref field) => argument.push_str(&format!(".{}", field)),
+                Access::Index(index) => argument.push_str(&format!("[{}]", index)),
+                Access::Key(ref key) => argument.push_str(&format!("[{:?}]", key)),
+            }
+        }
+        match usage.name {
+            Some(_) => write!(fmt, "argument {:?} ", argument)?,
+            None => write!(fmt, "argument {} ", argument)?,
+        }
+        let role = match usage.role {
+            Role::Format(name) => format!("formatted with {}", name),
+            Role::Width => "used as a width".to_owned(),
+            Role::Precision => "used as a precision".to_owned(),
+        };
+        match *self {
+            Difference::Missing(_) => fmt.write_str("is not used by the translation"),
+            Difference::Extra(_) => fmt.write_str("is not used by the source"),
+            Difference::NewRole(_) => write!(fmt, "is {} only by the translation", role),
+            Difference::MissingCount(_) => write!(fmt, "is {} only by the source", role),
+        }
+    }
+}
+
+/// Compare the usages of the arguments of a translation to those of its
+/// source, as returned by `PreparedFormat::usages` or `FormatBuf::usages`.
+///
+/// The translation is compatible if it uses the same arguments, formats
+/// each with only traits the source also uses for it, and uses the same
+/// arguments as widths and precisions. An argument reached through a path,
+/// as in `{user.name}`, counts as a different argument for each path. Each
+/// difference is reported once, at its first occurrence; differences in the
+/// source come first.
+pub fn compare(source: &[Usage], translation: &[Usage]) -> Vec<Difference> {
+    let uses = |usages: &[Usage], usage: &Usage| {
+        usages.iter().any(|u| u.idx == usage.idx && u.path == usage.path)
+    };
+    let uses_as = |usages: &[Usage], usage: &Usage| {
+        usages.iter().any(|u| u.idx == usage.idx && u.path == usage.path && u.role == usage.role)
+    };
+    let is_count = |role: Role| match role {
+        Role::Width | Role::Precision => true,
+        Role::Format(_) => false,
+    };
+
+    let mut differences = Vec::new();
+    for (i, usage) in source.iter().enumerate() {
+        if !uses(translation, usage) {
+            if !uses(&source[..i], usage) {
+                differences.push(Difference::Missing(usage.clone()));
+            }
+        } else if is_count(usage.role) && !uses_as(translation, usage) &&
+            !uses_as(&source[..i], usage)
+        {
+            differences.push(Difference::MissingCount(usage.clone()));
+        }
+    }
+    for (i, usage) in translation.iter().enumerate() {
+        if !uses(source, usage) {
+            if !uses(&translation[..i], usage) {
+                differences.push(Difference::Extra(usage.clone()));
+            }
+        } else if !uses_as(source, usage) && !uses_as(&translation[..i], usage) {
+            differences.push(Difference::NewRole(usage.clone()));
+        }
+    }
+    differences
+}
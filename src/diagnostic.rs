@@ -0,0 +1,119 @@
+//! Rendering of errors against the format string they arose from.
+use std::fmt::{self, Write};
+
+use {suggest, Error, Span};
+
+/// An `Error` displayed in the style of a compiler diagnostic, with the
+/// offending portion of the format string underlined.
+///
+/// Returned by `Error::render`.
+pub struct Diagnostic<'e, 'a: 'e> {
+    error: &'e Error<'a>,
+    spec: &'e str,
+}
+
+impl<'a> Error<'a> {
+    /// Render this error against the format string it arose from.
+    ///
+    /// The result displays the error message followed by the relevant line
+    /// of the format string, with the offending portion underlined, and any
+    /// note explaining the error:
+    ///
+    /// ```text
+    /// error: invalid format string: unmatched `}` found
+    ///   |
+    /// 1 | Hello, {}}!
+    ///   |          ^
+    ///   |
+    ///   = note: if you intended to print `}`, you can escape it using `}}`
+    /// ```
+    #[inline]
+    pub fn render<'e>(&'e self, spec: &'e str) -> Diagnostic<'e, 'a> {
+        Diagnostic {
+            error: self,
+            spec: spec,
+        }
+    }
+}
+
+impl<'e, 'a> fmt::Display for Diagnostic<'e, 'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self.error {
+            Error::BadSyntax(ref errors) => {
+                for (i, err) in errors.iter().enumerate() {
+                    if i > 0 {
+                        fmt.write_str("\n\n")?;
+                    }
+                    let message = format!("invalid format string: {}", err.description);
+                    let note = err.note.as_ref().map(|note| ("note", &note[..]));
+                    snippet(fmt, &message, self.spec, Some(err.span), note)?;
+                }
+                Ok(())
+            }
+            ref other => {
+                let message = Message(other).to_string();
+                let help = suggest::did_you_mean(other.suggestions());
+                let help = help.as_ref().map(|help| ("help", &help[..]));
+                snippet(fmt, &message, self.spec, other.span(), help)
+            }
+        }
+    }
+}
+
+/// The message of an error, without any suggestions.
+struct Message<'e, 'a: 'e>(&'e Error<'a>);
+
+impl<'e, 'a> fmt::Display for Message<'e, 'a> {
+    #[inline]
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        self.0.message(fmt)
+    }
+}
+
+/// Write a single message, followed by the line of `spec` containing `span`
+/// with the span underlined, followed by a labelled footer such as a note.
+fn snippet(fmt: &mut fmt::Formatter, message: &str, spec: &str, span: Option<Span>,
+           footer: Option<(&str, &str)>)
+    -> fmt::Result
+{
+    write!(fmt, "error: {}", message)?;
+
+    // The span may not be valid if the error is rendered against a different
+    // format string than the one it arose from.
+    let span = span.filter(|span| {
+        span.start <= span.end &&
+            spec.is_char_boundary(span.start) &&
+            spec.is_char_boundary(span.end)
+    });
+
+    let line_no = span.map_or(0, |span| spec[..span.start].matches('\n').count() + 1);
+    let gutter = line_no.to_string().len();
+
+    if let Some(span) = span {
+        let line_start = spec[..span.start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = spec[span.start..].find('\n').map_or(spec.len(), |i| span.start + i);
+        // Spans which continue onto later lines are underlined to the end of
+        // the first line only.
+        let end = ::std::cmp::min(span.end, line_end);
+
+        write!(fmt, "\n{:w$} |", "", w = gutter)?;
+        write!(fmt, "\n{} | {}", line_no, &spec[line_start..line_end])?;
+        write!(fmt, "\n{:w$} | ", "", w = gutter)?;
+        // Keep tabs so that the carets line up however they are displayed.
+        for c in spec[line_start..span.start].chars() {
+            fmt.write_char(if c == '\t' { '\t' } else { ' ' })?;
+        }
+        let carets = ::std::cmp::max(1, spec[span.start..end].chars().count());
+        for _ in 0..carets {
+            fmt.write_char('^')?;
+        }
+    }
+
+    if let Some((label, text)) = footer {
+        if span.is_some() {
+            write!(fmt, "\n{:w$} |", "", w = gutter)?;
+        }
+        write!(fmt, "\n{:w$} = {}: {}", "", label, text, w = gutter)?;
+    }
+    Ok(())
+}
@@ -1,87 +1,710 @@
 //! Type erasure for formattable types.
 use std::fmt;
+use std::mem;
+use std::borrow::Cow;
 use std::convert::TryFrom;
-use Error;
+use std::marker::PhantomData;
+use codegen::Child;
+use index::Elements;
+use {Error, FormatArgs, Span};
 
 type Func<T> = fn(&T, &mut fmt::Formatter) -> fmt::Result;
 
+/// Stand-in for the type of an erased value.
+pub struct Opaque {
+    _private: [u8; 0],
+}
+
+/// A type-erased value together with the function used to format it.
+#[derive(Copy, Clone)]
+pub struct Argument<'a> {
+    value: &'a Opaque,
+    formatter: Func<Opaque>,
+    count: Option<usize>,
+    compute: Option<Compute>,
+    text: Option<&'a str>,
+}
+
+impl<'a> Argument<'a> {
+    #[inline]
+    pub fn new<T>(value: &'a T, formatter: Func<T>) -> Argument<'a> {
+        // Function pointers differing only in the pointee type of a
+        // reference argument are ABI-compatible, so this is sound so long
+        // as the function is only ever called with the original value.
+        unsafe {
+            Argument {
+                value: mem::transmute::<&T, &Opaque>(value),
+                formatter: mem::transmute::<Func<T>, Func<Opaque>>(formatter),
+                count: None,
+                compute: None,
+                text: None,
+            }
+        }
+    }
+
+    /// Format the value the method computes from this value rather than
+    /// this value itself, if there is one.
+    #[inline]
+    pub fn computed(self, compute: Option<Compute>) -> Argument<'a> {
+        Argument { compute: compute, ..self }
+    }
+
+    /// Format the given text in place of a value which is absent.
+    #[inline]
+    pub fn text(text: &'a str) -> Argument<'a> {
+        Argument {
+            text: Some(text),
+            ..Argument::new(&(), <() as fmt::Debug>::fmt)
+        }
+    }
+
+    #[inline]
+    pub fn from_usize(value: &'a usize) -> Argument<'a> {
+        Argument {
+            count: Some(*value),
+            ..Argument::new(value, <usize as fmt::Display>::fmt)
+        }
+    }
+
+    /// An argument which is only used as the given count.
+    #[inline]
+    pub fn from_count(count: usize) -> Argument<'a> {
+        Argument {
+            count: Some(count),
+            ..Argument::new(&(), <() as fmt::Debug>::fmt)
+        }
+    }
+
+    #[inline]
+    pub fn as_usize(&self) -> Option<usize> {
+        self.count
+    }
+
+    #[inline]
+    pub fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.text, self.compute) {
+            (Some(text), _) => fmt::Display::fmt(text, f),
+            (None, Some(compute)) => (compute.call)(self.value, self.formatter, f),
+            (None, None) => (self.formatter)(self.value, f),
+        }
+    }
+}
+
+/// A method computing a child from its parent, as given by
+/// `#[format(method = "...")]`, which is called each time the child is
+/// formatted.
+#[doc(hidden)]
+#[derive(Copy, Clone)]
+pub struct Compute {
+    call: Call,
+}
+
+/// Calls a method on an erased parent and formats the result, given the
+/// erased formatting function of the result's type.
+#[doc(hidden)]
+pub type Call = fn(&Opaque, Func<Opaque>, &mut fmt::Formatter) -> fmt::Result;
+
+impl Compute {
+    #[inline]
+    pub fn new(call: Call) -> Compute {
+        Compute { call: call }
+    }
+}
+
+/// Call a method on a parent erased from a `&P`, and format the result with
+/// a formatting function erased from the `Traits` of its type.
+///
+/// # Safety
+///
+/// `parent` must have been erased from a `&P`, and `formatter` taken from
+/// a `Traits<R>`.
+#[doc(hidden)]
+#[inline]
+pub unsafe fn call_computed<'p, P, R>(method: fn(&'p P) -> R,
+                                      parent: &'p Opaque,
+                                      formatter: Func<Opaque>,
+                                      f: &mut fmt::Formatter)
+    -> fmt::Result
+{
+    let formatter = mem::transmute::<Func<Opaque>, Func<R>>(formatter);
+    formatter(&method(unerase(parent)), f)
+}
+
+/// Erase the type of a value's reference.
+///
+/// # Safety
+///
+/// The erased reference must only be restored as, or formatted as, a
+/// reference to `T`. In particular, when it is returned from
+/// `FormatArgs::get`, the `Traits` which `get_child` describes for that
+/// child must be those of `T`.
+#[inline]
+pub unsafe fn erase<T>(value: &T) -> &Opaque {
+    mem::transmute::<&T, &Opaque>(value)
+}
+
+/// Restore the type of a value's reference, which must have been erased from
+/// a reference of that type.
+#[inline]
+pub unsafe fn unerase<T>(value: &Opaque) -> &T {
+    mem::transmute::<&Opaque, &T>(value)
+}
+
+// Marker for the count entry of a `Probe`.
+#[doc(hidden)]
+pub struct Count;
+
+// Marker for the kind entry of a `Probe`.
+#[doc(hidden)]
+pub struct Classified;
+
+// Marker for the fields entry of a `Probe`.
+#[doc(hidden)]
+pub struct Nested;
+
+/// The children of a type implementing `FormatArgs`, through which a path
+/// such as `{user.name}` reaches them.
+#[doc(hidden)]
+pub struct Fields<T> {
+    pub validate_name: fn(&str) -> Option<usize>,
+    pub validate_index: fn(usize) -> bool,
+    pub names: fn() -> Cow<'static, [&'static str]>,
+    pub len: fn() -> usize,
+    pub get_child: fn(usize) -> Child,
+    pub get: fn(&T, usize) -> Option<&Opaque>,
+}
+
+impl<T> Copy for Fields<T> {}
+impl<T> Clone for Fields<T> {
+    fn clone(&self) -> Self { *self }
+}
+
+impl<T: FormatArgs> Fields<T> {
+    #[inline]
+    fn of() -> Self {
+        Fields {
+            validate_name: T::validate_name,
+            validate_index: T::validate_index,
+            names: T::names,
+            len: T::len,
+            get_child: T::get_child,
+            get: T::get,
+        }
+    }
+}
+
+impl<T> Fields<T> {
+    #[inline]
+    fn erase(self) -> Fields<Opaque> {
+        Fields {
+            get: unsafe {
+                mem::transmute::<
+                    fn(&T, usize) -> Option<&Opaque>,
+                    fn(&Opaque, usize) -> Option<&Opaque>
+                >(self.get)
+            },
+            validate_name: self.validate_name,
+            validate_index: self.validate_index,
+            names: self.names,
+            len: self.len,
+            get_child: self.get_child,
+        }
+    }
+}
+
+/// The broad kind of a parameter's type.
+///
+/// Used to warn about formatting options which have no effect on values of
+/// that kind.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Kind {
+    /// A primitive integer.
+    Integer,
+    /// A primitive floating-point number.
+    Float,
+    /// A string or character.
+    Text,
+    /// Any other type.
+    Other,
+}
+
+/// Types of a known `Kind`, including references to them.
+#[doc(hidden)]
+pub trait Classify {
+    const KIND: Kind;
+}
+
+macro_rules! classify {
+    ($kind:ident: $($t:ty),*) => {
+        $(impl Classify for $t {
+            const KIND: Kind = Kind::$kind;
+        })*
+    }
+}
+
+classify!(Integer: i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+classify!(Float: f32, f64);
+classify!(Text: str, String, char);
+
+impl<T: Classify + ?Sized> Classify for &T {
+    const KIND: Kind = T::KIND;
+}
+
+impl<T: Classify + ?Sized> Classify for &mut T {
+    const KIND: Kind = T::KIND;
+}
+
+impl<T: Classify + ?Sized> Classify for Box<T> {
+    const KIND: Kind = T::KIND;
+}
+
+/// The primitive integer types, whose values may be used as a width or
+/// precision count when they fit in a `usize`.
+///
+/// This trait is sealed, and cannot be implemented outside this crate.
+pub trait Integer: Copy + sealed::Sealed {
+    #[doc(hidden)]
+    fn to_usize(self) -> Option<usize>;
+}
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+macro_rules! integer {
+    ($($t:ty),*) => {
+        $(impl sealed::Sealed for $t {}
+        impl Integer for $t {
+            #[inline]
+            fn to_usize(self) -> Option<usize> {
+                usize::try_from(self).ok()
+            }
+        })*
+    }
+}
+
+integer!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+
+/// Type-level probe for the traits a type implements.
+///
+/// Used by the `rt_` macros and `#[derive(FormatArgs)]` to build `Traits`
+/// tables without specialization: the `Has*` traits are implemented for the
+/// probe itself only when the bound is satisfied, and the `Lacks*` traits
+/// for a reference to it unconditionally, so method resolution on
+/// `(&probe)` selects the former whenever it applies.
+#[doc(hidden)]
+pub struct Probe<F: ?Sized, T>(PhantomData<fn(&F, &T)>);
+
+impl<F: ?Sized, T> Probe<F, T> {
+    #[inline]
+    pub fn new() -> Self {
+        Probe(PhantomData)
+    }
+
+    #[inline]
+    pub fn of(_: &T) -> Self {
+        Probe(PhantomData)
+    }
+
+    #[inline]
+    pub fn like<G: ?Sized>(_: &Probe<G, T>) -> Self {
+        Probe(PhantomData)
+    }
+
+    #[inline]
+    pub fn returned<'a, P: 'a>(_: fn(&'a P) -> T) -> Self {
+        Probe(PhantomData)
+    }
+}
+
+impl<F: ?Sized, T> Default for Probe<F, T> {
+    #[inline]
+    fn default() -> Self {
+        Probe::new()
+    }
+}
+
+#[doc(hidden)]
+pub trait Has<T> {
+    fn get(&self) -> Option<Func<T>>;
+}
+
+#[doc(hidden)]
+pub trait Lacks<T> {
+    fn get(&self) -> Option<Func<T>>;
+}
+
+impl<F: ?Sized, T> Lacks<T> for &Probe<F, T> {
+    #[inline]
+    fn get(&self) -> Option<Func<T>> { None }
+}
+
+#[doc(hidden)]
+pub trait HasCount<T> {
+    fn count(&self) -> Option<fn(&T) -> Option<usize>>;
+}
+
+#[doc(hidden)]
+pub trait LacksCount<T> {
+    fn count(&self) -> Option<fn(&T) -> Option<usize>>;
+}
+
+impl<T: Integer> HasCount<T> for Probe<Count, T> {
+    #[inline]
+    fn count(&self) -> Option<fn(&T) -> Option<usize>> {
+        Some(to_usize::<T>)
+    }
+}
+
+impl<T> LacksCount<T> for &Probe<Count, T> {
+    #[inline]
+    fn count(&self) -> Option<fn(&T) -> Option<usize>> { None }
+}
+
+#[doc(hidden)]
+pub trait HasKind<T> {
+    fn kind(&self) -> Kind;
+}
+
+#[doc(hidden)]
+pub trait LacksKind<T> {
+    fn kind(&self) -> Kind;
+}
+
+impl<T: Classify> HasKind<T> for Probe<Classified, T> {
+    #[inline]
+    fn kind(&self) -> Kind { T::KIND }
+}
+
+impl<T> LacksKind<T> for &Probe<Classified, T> {
+    #[inline]
+    fn kind(&self) -> Kind { Kind::Other }
+}
+
+#[doc(hidden)]
+pub trait HasFields<T> {
+    fn fields(&self) -> Option<Fields<T>>;
+}
+
+#[doc(hidden)]
+pub trait LacksFields<T> {
+    fn fields(&self) -> Option<Fields<T>>;
+}
+
+impl<T: FormatArgs> HasFields<T> for Probe<Nested, T> {
+    #[inline]
+    fn fields(&self) -> Option<Fields<T>> { Some(Fields::of()) }
+}
+
+impl<T> LacksFields<T> for &Probe<Nested, T> {
+    #[inline]
+    fn fields(&self) -> Option<Fields<T>> { None }
+}
+
+#[inline]
+fn to_usize<T: Integer>(t: &T) -> Option<usize> {
+    t.to_usize()
+}
+
+#[cfg(feature = "nightly")]
 trait AsUsize {
-    fn as_usize(&self) -> Option<usize>;
+    fn count() -> Option<fn(&Self) -> Option<usize>>;
 }
+#[cfg(feature = "nightly")]
 impl<T> AsUsize for T {
     #[inline]
-    default fn as_usize(&self) -> Option<usize> { None }
+    default fn count() -> Option<fn(&Self) -> Option<usize>> { None }
 }
-impl<T> AsUsize for T where usize: TryFrom<T>, T: Copy {
+#[cfg(feature = "nightly")]
+impl<T: Integer> AsUsize for T {
     #[inline]
-    fn as_usize(&self) -> Option<usize> {
-        usize::try_from(*self).ok()
+    fn count() -> Option<fn(&Self) -> Option<usize>> {
+        Some(to_usize::<T>)
     }
 }
 
+#[cfg(feature = "nightly")]
+trait KindOf {
+    fn kind() -> Kind;
+}
+#[cfg(feature = "nightly")]
+impl<T> KindOf for T {
+    #[inline]
+    default fn kind() -> Kind { Kind::Other }
+}
+#[cfg(feature = "nightly")]
+impl<T: Classify> KindOf for T {
+    #[inline]
+    fn kind() -> Kind { T::KIND }
+}
+
+#[cfg(feature = "nightly")]
+trait FieldsOf: Sized {
+    fn fields() -> Option<Fields<Self>>;
+}
+#[cfg(feature = "nightly")]
+impl<T> FieldsOf for T {
+    #[inline]
+    default fn fields() -> Option<Fields<Self>> { None }
+}
+#[cfg(feature = "nightly")]
+impl<T: FormatArgs> FieldsOf for T {
+    #[inline]
+    fn fields() -> Option<Fields<Self>> { Some(Fields::of()) }
+}
+
 macro_rules! traits {
-    ($($string:pat, $upper:ident, $lower:ident;)*) => {
+    ($($string:tt, $upper:ident, $lower:ident;)*) => {
         $(
+            #[cfg(feature = "nightly")]
             trait $upper {
                 fn $lower() -> Option<Func<Self>>;
             }
+            #[cfg(feature = "nightly")]
             impl<T> $upper for T {
                 #[inline]
                 default fn $lower() -> Option<Func<Self>> { None }
             }
+            #[cfg(feature = "nightly")]
             impl<T: fmt::$upper> $upper for T {
                 #[inline]
                 fn $lower() -> Option<Func<Self>> {
                     Some(<Self as fmt::$upper>::fmt)
                 }
             }
+
+            impl<T: fmt::$upper> Has<T> for Probe<dyn fmt::$upper, T> {
+                #[inline]
+                fn get(&self) -> Option<Func<T>> {
+                    Some(<T as fmt::$upper>::fmt)
+                }
+            }
         )*
 
-        pub trait Format {
-            fn as_usize(&self) -> Option<usize>;
-            fn by_name<'n>(&self, name: &'n str, idx: usize) -> Result<fmt::ArgumentV1, Error<'n>>;
+        /// The set of formatting traits supported by a parameter type.
+        ///
+        /// The `rt_` macros and `#[derive(FormatArgs)]` build these tables
+        /// automatically. They may also be built by hand, registering each
+        /// supported trait explicitly, for use with `Param::normal_with` and
+        /// `Param::named_with`.
+        pub struct Traits<T> {
+            $($lower: Option<Func<T>>,)*
+            count: Option<fn(&T) -> Option<usize>>,
+            kind: Kind,
+            fields: Option<Fields<T>>,
+            elements: Option<Elements<T>>,
         }
 
-        impl<T> Format for T {
+        impl<T> Traits<T> {
+            /// Construct a table supporting no formatting traits.
+            #[inline]
+            pub fn new() -> Self {
+                Traits {
+                    $($lower: None,)*
+                    count: None,
+                    kind: Kind::Other,
+                    fields: None,
+                    elements: None,
+                }
+            }
+
+            /// Construct a table of every formatting trait `T` implements,
+            /// as detected through specialization.
+            #[cfg(feature = "nightly")]
             #[inline]
-            fn as_usize(&self) -> Option<usize> {
-                AsUsize::as_usize(self)
+            pub fn detect() -> Self {
+                Traits {
+                    $($lower: <T as $upper>::$lower(),)*
+                    count: <T as AsUsize>::count(),
+                    kind: <T as KindOf>::kind(),
+                    fields: <T as FieldsOf>::fields(),
+                    elements: ::index::detect_elements(),
+                }
             }
-            fn by_name<'n>(&self, name: &'n str, idx: usize) -> Result<fmt::ArgumentV1, Error<'n>> {
+
+            $(
+                /// Register support for the formatting trait of this name.
+                #[inline]
+                pub fn $lower(mut self) -> Self where T: fmt::$upper {
+                    self.$lower = Some(<T as fmt::$upper>::fmt);
+                    self
+                }
+            )*
+
+            /// Register a function to format with in place of the type's
+            /// own implementation of the named formatting trait, such as
+            /// `"Display"` or `"LowerHex"`, whether or not the type
+            /// implements it.
+            ///
+            /// Panics if no formatting trait has the given name.
+            #[inline]
+            pub fn using(mut self, name: &str, f: fn(&T, &mut fmt::Formatter) -> fmt::Result) -> Self {
+                match name {
+                    $(stringify!($upper) => self.$lower = Some(f),)*
+                    _ => panic!("no formatting trait is named {:?}", name),
+                }
+                self
+            }
+
+            /// Register support for use as a width or precision count.
+            #[inline]
+            pub fn count(mut self) -> Self where T: Integer {
+                self.count = Some(to_usize::<T>);
+                self
+            }
+
+            /// Register the children of a type implementing `FormatArgs`,
+            /// so that paths such as `{0.name}` may reach them.
+            #[inline]
+            pub fn fields(mut self) -> Self where T: FormatArgs {
+                self.fields = Some(Fields::of());
+                self
+            }
+
+            #[doc(hidden)]
+            #[inline]
+            #[allow(clippy::too_many_arguments)]
+            pub fn probed($($lower: Option<Func<T>>,)*
+                          count: Option<fn(&T) -> Option<usize>>,
+                          kind: Kind,
+                          fields: Option<Fields<T>>,
+                          elements: Option<Elements<T>>)
+                -> Self
+            {
+                Traits {
+                    $($lower: $lower,)*
+                    count: count,
+                    kind: kind,
+                    fields: fields,
+                    elements: elements,
+                }
+            }
+
+            /// Record the kind of the type, so that formatting options which
+            /// have no effect on it may be warned about.
+            #[inline]
+            pub fn of_kind(mut self, kind: Kind) -> Self {
+                self.kind = kind;
+                self
+            }
+
+            /// The kind of the type, if recorded.
+            #[inline]
+            pub fn kind(&self) -> Kind {
+                self.kind
+            }
+
+            /// Look up the formatting function for a format specifier,
+            /// reporting errors at the given span.
+            #[doc(hidden)]
+            pub fn by_name<'n>(&self, name: &'n str, idx: usize, span: Span)
+                -> Result<Func<T>, Error<'n>>
+            {
                 match name {
                     $(
-                        $string => match <Self as $upper>::$lower() {
-                            Some(f) => Ok(fmt::ArgumentV1::new(self, f)),
+                        $string => match self.$lower {
+                            Some(f) => Ok(f),
                             None => Err(Error::UnsatisfiedFormat {
                                 idx: idx,
                                 must_implement: stringify!($upper),
+                                span: span,
                             }),
                         },
                     )*
-                    _ => Err(Error::NoSuchFormat(name)),
+                    _ => {
+                        // Display, with no name, is never a likely suggestion.
+                        let names: Vec<_> = [$($string),*].iter().cloned()
+                            .filter(|name| !name.is_empty())
+                            .collect();
+                        let suggestions = ::suggest::similar(name, &names);
+                        Err(Error::NoSuchFormat(name.into(), span, suggestions))
+                    }
+                }
+            }
+
+            /// Whether use as a width or precision count is registered.
+            #[inline]
+            pub fn is_count(&self) -> bool {
+                self.count.is_some()
+            }
+
+            /// The conversion of a value to a count, if registered.
+            #[doc(hidden)]
+            #[inline]
+            pub fn get_count(&self) -> Option<fn(&T) -> Option<usize>> {
+                self.count
+            }
+
+            /// The children of the type, if registered.
+            #[doc(hidden)]
+            #[inline]
+            pub fn get_fields(&self) -> Option<Fields<T>> {
+                self.fields
+            }
+
+            /// Forget the children and elements of the type, which cannot be
+            /// reached when its values are computed.
+            #[doc(hidden)]
+            #[inline]
+            pub fn without_paths(mut self) -> Self {
+                self.fields = None;
+                self.elements = None;
+                self
+            }
+
+            /// The elements of the type, if it is a sequence or map.
+            #[doc(hidden)]
+            #[inline]
+            pub fn get_elements(&self) -> Option<Elements<T>> {
+                self.elements
+            }
+
+            /// Interpret a value as a count, if supported.
+            #[inline]
+            pub fn as_usize(&self, t: &T) -> Option<usize> {
+                self.count.and_then(|f| f(t))
+            }
+
+            /// Erase the type this table applies to.
+            #[inline]
+            pub fn erase(self) -> Traits<Opaque> {
+                unsafe {
+                    Traits {
+                        $($lower: self.$lower.map(|f| mem::transmute::<Func<T>, Func<Opaque>>(f)),)*
+                        count: self.count.map(|f| mem::transmute::<
+                            fn(&T) -> Option<usize>,
+                            fn(&Opaque) -> Option<usize>
+                        >(f)),
+                        kind: self.kind,
+                        fields: self.fields.map(Fields::erase),
+                        elements: self.elements.map(Elements::erase),
+                    }
                 }
             }
         }
 
-        pub fn codegen_get_child<'n, T: ::FormatArgs>(name: &'n str, idx: usize)
-            -> Result<fn(&T, &mut fmt::Formatter) -> fmt::Result, Error>
-        {
-            match name {
-                $(
-                    $string => match T::get_child::<dyn fmt::$upper>(idx) {
-                        Some(f) => Ok(f),
-                        None => Err(Error::UnsatisfiedFormat {
-                            idx: idx,
-                            must_implement: stringify!($upper),
-                        })
-                    },
-                )*
-                _ => Err(Error::NoSuchFormat(name)),
+        /// The name of the formatting trait a format specifier requires.
+        pub fn trait_name(spec: &str) -> Option<&'static str> {
+            match spec {
+                $($string => Some(stringify!($upper)),)*
+                _ => None,
             }
         }
     }
 }
 
+impl<T> Copy for Traits<T> {}
+impl<T> Clone for Traits<T> {
+    fn clone(&self) -> Self { *self }
+}
+
+impl<T> Default for Traits<T> {
+    #[inline]
+    fn default() -> Self {
+        Traits::new()
+    }
+}
+
 traits! {
     "", Display, display;
     "?", Debug, debug;
@@ -93,3 +716,81 @@ traits! {
     "x", LowerHex, lower_hex;
     "X", UpperHex, upper_hex;
 }
+
+/// The `Traits` of the type of a value, as detected through specialization.
+#[cfg(feature = "nightly")]
+#[doc(hidden)]
+#[inline]
+pub fn detect_of<T>(_: &T) -> Traits<T> {
+    Traits::detect()
+}
+
+/// The `Traits` of the type a method returns, as detected through
+/// specialization.
+#[cfg(feature = "nightly")]
+#[doc(hidden)]
+#[inline]
+pub fn detect_returned<'a, P: 'a, R>(_: fn(&'a P) -> R) -> Traits<R> {
+    Traits::detect()
+}
+
+/// Build the `Traits` table for a type, for the type of a value, or for the
+/// type a method returns.
+///
+/// With the `nightly` feature, the table is detected through specialization,
+/// which unlike probing also sees the traits of generic parameters.
+#[cfg(feature = "nightly")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __rt_traits {
+    (type $t:ty) => { $crate::Traits::<$t>::detect() };
+    (of $v:expr) => { $crate::codegen::detect_of($v) };
+    (returned $f:expr) => { $crate::codegen::detect_returned($f) };
+}
+
+/// Build the `Traits` table for a type, for the type of a value, or for the
+/// type a method returns.
+///
+/// The list of traits here must be kept in sync with the `traits!` table.
+#[cfg(not(feature = "nightly"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __rt_traits {
+    (type $t:ty) => { $crate::__rt_traits!(@[$t] new() elements) };
+    (of $v:expr) => { $crate::__rt_traits!(@[_] of($v) elements) };
+    (returned $f:expr) => { $crate::__rt_traits!(@[_] returned($f) elements) };
+    // The elements of a sequence or map, or the contents of an `Option`,
+    // are probed through a closure, to which the probe of the sequence, map,
+    // or `Option` gives the element type. Their own elements are not probed,
+    // to bound the expansion.
+    (@elements [$t:ty] $ctor:ident $args:tt) => {{
+        #[allow(unused_imports)]
+        use $crate::codegen::{HasSequence, LacksSequence, HasMap, LacksMap, HasOption, LacksOption};
+        (&$crate::codegen::Probe::<$crate::codegen::Sequenced, $t>::$ctor $args)
+            .sequence(|element| $crate::__rt_traits!(@[_] like(element) leaf).erase())
+            .or((&$crate::codegen::Probe::<$crate::codegen::Keyed, $t>::$ctor $args)
+                .map(|element| $crate::__rt_traits!(@[_] like(element) leaf).erase()))
+            .or((&$crate::codegen::Probe::<$crate::codegen::Optional, $t>::$ctor $args)
+                .option(|element| $crate::__rt_traits!(@[_] like(element) leaf).erase()))
+    }};
+    (@leaf [$t:ty] $ctor:ident $args:tt) => { None };
+    (@[$t:ty] $ctor:ident $args:tt $depth:ident) => {{
+        #[allow(unused_imports)]
+        use $crate::codegen::{Has, Lacks, HasCount, LacksCount, HasKind, LacksKind, HasFields, LacksFields};
+        $crate::Traits::probed(
+            (&$crate::codegen::Probe::<dyn $crate::codegen::Display, $t>::$ctor $args).get(),
+            (&$crate::codegen::Probe::<dyn $crate::codegen::Debug, $t>::$ctor $args).get(),
+            (&$crate::codegen::Probe::<dyn $crate::codegen::LowerExp, $t>::$ctor $args).get(),
+            (&$crate::codegen::Probe::<dyn $crate::codegen::UpperExp, $t>::$ctor $args).get(),
+            (&$crate::codegen::Probe::<dyn $crate::codegen::Octal, $t>::$ctor $args).get(),
+            (&$crate::codegen::Probe::<dyn $crate::codegen::Pointer, $t>::$ctor $args).get(),
+            (&$crate::codegen::Probe::<dyn $crate::codegen::Binary, $t>::$ctor $args).get(),
+            (&$crate::codegen::Probe::<dyn $crate::codegen::LowerHex, $t>::$ctor $args).get(),
+            (&$crate::codegen::Probe::<dyn $crate::codegen::UpperHex, $t>::$ctor $args).get(),
+            (&$crate::codegen::Probe::<$crate::codegen::Count, $t>::$ctor $args).count(),
+            (&$crate::codegen::Probe::<$crate::codegen::Classified, $t>::$ctor $args).kind(),
+            (&$crate::codegen::Probe::<$crate::codegen::Nested, $t>::$ctor $args).fields(),
+            $crate::__rt_traits!(@$depth [$t] $ctor $args),
+        )
+    }};
+}
@@ -23,6 +23,8 @@
        html_playground_url = "https://play.rust-lang.org/",
        test(attr(deny(warnings))))]*/
 #![deny(warnings)]
+// Kept as close to upstream as possible, so not held to clippy's lints.
+#![allow(clippy::all)]
 
 extern crate unicode_xid;
 
@@ -31,7 +33,9 @@ pub use self::Position::*;
 pub use self::Alignment::*;
 pub use self::Flag::*;
 pub use self::Count::*;
+pub use self::Access::*;
 
+use std::convert::TryFrom;
 use std::str;
 use std::string;
 use std::iter;
@@ -40,7 +44,7 @@ use self::unicode_xid::UnicodeXID;
 
 /// A piece is a portion of the format string which represents the next part
 /// to emit. These are emitted as a stream by the `Parser` class.
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Clone, PartialEq)]
 pub enum Piece<'a> {
     /// A literal string which should directly be emitted
     String(&'a str),
@@ -49,11 +53,44 @@ pub enum Piece<'a> {
     NextArgument(Argument<'a>),
 }
 
+/// A range of bytes within the format string.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct InnerSpan {
+    /// The byte offset of the start of the range
+    pub start: usize,
+    /// The byte offset just past the end of the range
+    pub end: usize,
+}
+
+impl InnerSpan {
+    /// Creates a new span from the given byte offsets
+    pub fn new(start: usize, end: usize) -> InnerSpan {
+        InnerSpan { start: start, end: end }
+    }
+}
+
+/// An error encountered while parsing a format string.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ParseError {
+    /// The main error message
+    pub description: string::String,
+    /// An optional note elaborating on the error
+    pub note: Option<string::String>,
+    /// The portion of the format string the error refers to
+    pub span: InnerSpan,
+}
+
 /// Representation of an argument specification.
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Clone, PartialEq)]
 pub struct Argument<'a> {
     /// Where to find this argument
     pub position: Position<'a>,
+    /// The span of the position, which is empty if it was implicit
+    pub position_span: InnerSpan,
+    /// The path from the argument to the value to format, as in `a.b[0]`
+    pub path: Vec<Segment<'a>>,
+    /// The span of the whole argument, including its braces
+    pub span: InnerSpan,
     /// How to format the argument
     pub format: FormatSpec<'a>,
 }
@@ -71,10 +108,21 @@ pub struct FormatSpec<'a> {
     pub precision: Count<'a>,
     /// The string width requested for the resulting format
     pub width: Count<'a>,
+    /// The span of the precision, including its `.`, if specified
+    pub precision_span: Option<InnerSpan>,
+    /// The span of the width, if specified
+    pub width_span: Option<InnerSpan>,
     /// The descriptor string representing the name of the format desired for
     /// this argument, this can be empty or any number of characters, although
     /// it is required to be one word.
     pub ty: &'a str,
+    /// The span of the descriptor string, if it is not empty
+    pub ty_span: Option<InnerSpan>,
+    /// The text to format in place of an absent value, as in `{name|none}`,
+    /// in which case the other options are the defaults
+    pub fallback: Option<&'a str>,
+    /// The span of the fallback, including its `|`, if specified
+    pub fallback_span: Option<InnerSpan>,
 }
 
 /// Enum describing where an argument for a format can be located.
@@ -86,6 +134,27 @@ pub enum Position<'a> {
     ArgumentNamed(&'a str),
 }
 
+/// A step along the path from an argument to the value to format.
+#[derive(Copy, Clone, PartialEq)]
+pub struct Segment<'a> {
+    /// What to access
+    pub access: Access<'a>,
+    /// The span of the segment, including its `.` or brackets
+    pub span: InnerSpan,
+}
+
+/// Enum describing how a segment of a path is accessed.
+#[derive(Copy, Clone, PartialEq)]
+pub enum Access<'a> {
+    /// A field, by name or by index, as in `.name` or `.0`.
+    Field(&'a str),
+    /// An element of a sequence, counting from the end if negative, as in
+    /// `[0]` or `[-1]`.
+    Index(isize),
+    /// An element of a map, by the text between the quotes of `["key"]`.
+    Key(&'a str),
+}
+
 /// Enum of alignments which are supported.
 #[derive(Copy, Clone, PartialEq)]
 pub enum Alignment {
@@ -139,7 +208,7 @@ pub struct Parser<'a> {
     input: &'a str,
     cur: iter::Peekable<str::CharIndices<'a>>,
     /// Error messages accumulated during parsing
-    pub errors: Vec<(string::String, Option<string::String>)>,
+    pub errors: Vec<ParseError>,
     /// Current position of implicit positional argument pointer
     curarg: usize,
 }
@@ -155,9 +224,10 @@ impl<'a> Iterator for Parser<'a> {
                     if self.consume('{') {
                         Some(String(self.string(pos + 1)))
                     } else {
-                        let ret = Some(NextArgument(self.argument()));
-                        self.must_consume('}');
-                        ret
+                        let mut arg = self.argument(pos);
+                        self.must_consume('}', pos);
+                        arg.span.end = self.offset();
+                        Some(NextArgument(arg))
                     }
                 }
                 '}' => {
@@ -167,7 +237,8 @@ impl<'a> Iterator for Parser<'a> {
                     } else {
                         self.err_with_note("unmatched `}` found",
                                            "if you intended to print `}`, \
-                                           you can escape it using `}}`");
+                                           you can escape it using `}}`",
+                                           InnerSpan::new(pos, pos + 1));
                         None
                     }
                 }
@@ -193,15 +264,36 @@ impl<'a> Parser<'a> {
     /// Notifies of an error. The message doesn't actually need to be of type
     /// String, but I think it does when this eventually uses conditions so it
     /// might as well start using it now.
-    fn err(&mut self, msg: &str) {
-        self.errors.push((msg.to_owned(), None));
+    fn err(&mut self, msg: &str, span: InnerSpan) {
+        self.errors.push(ParseError {
+            description: msg.to_owned(),
+            note: None,
+            span: span,
+        });
     }
 
     /// Notifies of an error. The message doesn't actually need to be of type
     /// String, but I think it does when this eventually uses conditions so it
     /// might as well start using it now.
-    fn err_with_note(&mut self, msg: &str, note: &str) {
-        self.errors.push((msg.to_owned(), Some(note.to_owned())));
+    fn err_with_note(&mut self, msg: &str, note: &str, span: InnerSpan) {
+        self.errors.push(ParseError {
+            description: msg.to_owned(),
+            note: Some(note.to_owned()),
+            span: span,
+        });
+    }
+
+    /// Returns the byte offset of the current position.
+    fn offset(&mut self) -> usize {
+        match self.cur.peek() {
+            Some(&(pos, _)) => pos,
+            None => self.input.len(),
+        }
+    }
+
+    /// Returns the span from the given byte offset to the current position.
+    fn span_from(&mut self, start: usize) -> InnerSpan {
+        InnerSpan::new(start, self.offset())
     }
 
     /// Optionally consumes the specified character. If the character is not at
@@ -221,22 +313,26 @@ impl<'a> Parser<'a> {
     }
 
     /// Forces consumption of the specified character. If the character is not
-    /// found, an error is emitted.
-    fn must_consume(&mut self, c: char) {
+    /// found, an error is emitted. If the string was terminated instead, the
+    /// error spans from `start` to the end of the string.
+    fn must_consume(&mut self, c: char, start: usize) {
         self.ws();
-        if let Some(&(_, maybe)) = self.cur.peek() {
+        if let Some(&(pos, maybe)) = self.cur.peek() {
             if c == maybe {
                 self.cur.next();
             } else {
-                self.err(&format!("expected `{:?}`, found `{:?}`", c, maybe));
+                self.err(&format!("expected `{:?}`, found `{:?}`", c, maybe),
+                         InnerSpan::new(pos, pos + maybe.len_utf8()));
             }
         } else {
             let msg = &format!("expected `{:?}` but string was terminated", c);
+            let span = self.span_from(start);
             if c == '}' {
                 self.err_with_note(msg,
-                                   "if you intended to print `{`, you can escape it using `{{`");
+                                   "if you intended to print `{`, you can escape it using `{{`",
+                                   span);
             } else {
-                self.err(msg);
+                self.err(msg, span);
             }
         }
     }
@@ -271,9 +367,12 @@ impl<'a> Parser<'a> {
     }
 
     /// Parses an Argument structure, or what's contained within braces inside
-    /// the format string
-    fn argument(&mut self) -> Argument<'a> {
+    /// the format string. `start` is the offset of the opening brace.
+    fn argument(&mut self, start: usize) -> Argument<'a> {
+        let position_start = self.offset();
         let pos = self.position();
+        let position_span = self.span_from(position_start);
+        let path = if pos.is_some() { self.path() } else { Vec::new() };
         let format = self.format();
 
         // Resolve position after parsing format spec.
@@ -288,6 +387,9 @@ impl<'a> Parser<'a> {
 
         Argument {
             position: pos,
+            position_span: position_span,
+            path: path,
+            span: self.span_from(start),
             format: format,
         }
     }
@@ -301,7 +403,7 @@ impl<'a> Parser<'a> {
             Some(ArgumentIs(i))
         } else {
             match self.cur.peek() {
-                Some(&(_, c)) if c.is_alphabetic() => Some(ArgumentNamed(self.word())),
+                Some(&(_, c)) if c.is_alphabetic() => Some(ArgumentNamed(self.name())),
 
                 // This is an `ArgumentNext`.
                 // Record the fact and do the resolution after parsing the
@@ -311,6 +413,76 @@ impl<'a> Parser<'a> {
         }
     }
 
+    /// Parses the path following an explicit position, which is a sequence of
+    /// fields such as `.name` or `.0` and elements such as `[0]` or `["key"]`.
+    fn path(&mut self) -> Vec<Segment<'a>> {
+        let mut path = Vec::new();
+        loop {
+            let start = self.offset();
+            let access = if self.consume('.') {
+                let field_start = self.offset();
+                let field = if self.integer().is_some() {
+                    &self.input[field_start..self.offset()]
+                } else {
+                    self.name()
+                };
+                if field.is_empty() {
+                    let span = self.span_from(start);
+                    self.err("expected a field name or index after `.`", span);
+                    return path;
+                }
+                Field(field)
+            } else if self.consume('[') {
+                match self.element() {
+                    Some(access) => {
+                        self.must_consume(']', start);
+                        access
+                    }
+                    None => {
+                        let span = self.span_from(start);
+                        self.err("expected an index or a quoted key after `[`", span);
+                        return path;
+                    }
+                }
+            } else {
+                return path;
+            };
+            path.push(Segment {
+                access: access,
+                span: self.span_from(start),
+            });
+        }
+    }
+
+    /// Parses the index or quoted key of an element at the current position,
+    /// following its `[`.
+    fn element(&mut self) -> Option<Access<'a>> {
+        let start = self.offset();
+        if self.consume('"') {
+            let key_start = self.offset();
+            while let Some(&(pos, c)) = self.cur.peek() {
+                self.cur.next();
+                if c == '"' {
+                    return Some(Key(&self.input[key_start..pos]));
+                }
+            }
+            let span = self.span_from(start);
+            self.err("unterminated key", span);
+            return Some(Key(&self.input[key_start..]));
+        }
+        let negative = self.consume('-');
+        let i = self.integer()?;
+        match isize::try_from(i) {
+            Ok(i) if negative => Some(Index(-i)),
+            Ok(i) => Some(Index(i)),
+            Err(_) => {
+                let span = self.span_from(start);
+                self.err("index out of range", span);
+                Some(Index(0))
+            }
+        }
+    }
+
     /// Parses a format specifier at the current position, returning all of the
     /// relevant information in the FormatSpec struct.
     fn format(&mut self) -> FormatSpec<'a> {
@@ -320,12 +492,36 @@ impl<'a> Parser<'a> {
             flags: 0,
             precision: CountImplied,
             width: CountImplied,
+            precision_span: None,
+            width_span: None,
             ty: &self.input[..0],
+            ty_span: None,
+            fallback: None,
+            fallback_span: None,
         };
-        if !self.consume(':') {
-            return spec;
+        // A `|` in place of the `:` of a format spec begins the text to format
+        // in place of an absent value, which runs up to the `:` of any
+        // options, or else to the closing brace.
+        let start = self.offset();
+        if self.consume('|') {
+            let text_start = self.offset();
+            while let Some(&(_, c)) = self.cur.peek() {
+                if c == '}' || c == ':' {
+                    break;
+                }
+                self.cur.next();
+            }
+            spec.fallback = Some(&self.input[text_start..self.offset()]);
+            spec.fallback_span = Some(self.span_from(start));
         }
+        if self.consume(':') {
+            self.spec(&mut spec);
+        }
+        spec
+    }
 
+    /// Parses the options of a format specifier following its `:`.
+    fn spec(&mut self, spec: &mut FormatSpec<'a>) {
         // fill character
         if let Some(&(_, c)) = self.cur.peek() {
             match self.cur.clone().skip(1).next() {
@@ -356,6 +552,7 @@ impl<'a> Parser<'a> {
         }
         // Width and precision
         let mut havewidth = false;
+        let width_start = self.offset();
         if self.consume('0') {
             // small ambiguity with '0$' as a format string. In theory this is a
             // '0' flag and then an ill-formatted format string with just a '$'
@@ -363,14 +560,20 @@ impl<'a> Parser<'a> {
             // no '0' flag and '0$' as the width instead.
             if self.consume('$') {
                 spec.width = CountIsParam(0);
+                spec.width_span = Some(self.span_from(width_start));
                 havewidth = true;
             } else {
                 spec.flags |= 1 << (FlagSignAwareZeroPad as u32);
             }
         }
         if !havewidth {
+            let start = self.offset();
             spec.width = self.count();
+            if spec.width != CountImplied {
+                spec.width_span = Some(self.span_from(start));
+            }
         }
+        let precision_start = self.offset();
         if self.consume('.') {
             if self.consume('*') {
                 // Resolve `CountIsNextParam`.
@@ -381,14 +584,20 @@ impl<'a> Parser<'a> {
             } else {
                 spec.precision = self.count();
             }
+            if spec.precision != CountImplied {
+                spec.precision_span = Some(self.span_from(precision_start));
+            }
         }
         // Finally the actual format specifier
+        let ty_start = self.offset();
         if self.consume('?') {
             spec.ty = "?";
         } else {
             spec.ty = self.word();
         }
-        spec
+        if !spec.ty.is_empty() {
+            spec.ty_span = Some(self.span_from(ty_start));
+        }
     }
 
     /// Parses a Count parameter at the current position. This does not check
@@ -403,7 +612,7 @@ impl<'a> Parser<'a> {
             }
         } else {
             let tmp = self.cur.clone();
-            let word = self.word();
+            let word = self.name();
             if word.is_empty() {
                 self.cur = tmp;
                 CountImplied
@@ -418,6 +627,30 @@ impl<'a> Parser<'a> {
         }
     }
 
+    /// Parses a name starting at the current position, which is a word or
+    /// several joined by single hyphens, as in `user-name`.
+    fn name(&mut self) -> &'a str {
+        let start = self.offset();
+        if self.word().is_empty() {
+            return &self.input[..0];
+        }
+        loop {
+            let mut next = self.cur.clone();
+            match (next.next(), next.next()) {
+                (Some((_, '-')), Some((_, c))) if UnicodeXID::is_xid_continue(c) => {
+                    self.cur.next();
+                    while let Some(&(_, c)) = self.cur.peek() {
+                        if !UnicodeXID::is_xid_continue(c) {
+                            break;
+                        }
+                        self.cur.next();
+                    }
+                }
+                _ => return &self.input[start..self.offset()],
+            }
+        }
+    }
+
     /// Parses a word starting at the current position. A word is considered to
     /// be an alphabetic character followed by any number of alphanumeric
     /// characters.
@@ -479,10 +712,25 @@ mod tests {
             flags: 0,
             precision: CountImplied,
             width: CountImplied,
+            precision_span: None,
+            width_span: None,
             ty: "",
+            ty_span: None,
+            fallback: None,
+            fallback_span: None,
         };
     }
 
+    fn sp(start: usize, end: usize) -> InnerSpan {
+        InnerSpan::new(start, end)
+    }
+
+    fn errspans(s: &str) -> Vec<InnerSpan> {
+        let mut p = Parser::new(s);
+        while let Some(_) = p.next() {}
+        p.errors.iter().map(|e| e.span).collect()
+    }
+
     fn musterr(s: &str) {
         let mut p = Parser::new(s);
         p.next();
@@ -525,6 +773,9 @@ mod tests {
         same("{}",
              &[NextArgument(Argument {
                    position: ArgumentIs(0),
+                   position_span: sp(1, 1),
+                   path: vec![],
+                   span: sp(0, 2),
                    format: fmtdflt(),
                })]);
     }
@@ -533,6 +784,9 @@ mod tests {
         same("{3}",
              &[NextArgument(Argument {
                    position: ArgumentIs(3),
+                   position_span: sp(1, 2),
+                   path: vec![],
+                   span: sp(0, 3),
                    format: fmtdflt(),
                })]);
     }
@@ -541,6 +795,9 @@ mod tests {
         same("{3:}",
              &[NextArgument(Argument {
                    position: ArgumentIs(3),
+                   position_span: sp(1, 2),
+                   path: vec![],
+                   span: sp(0, 4),
                    format: fmtdflt(),
                })]);
     }
@@ -549,13 +806,21 @@ mod tests {
         same("{3:a}",
              &[NextArgument(Argument {
                    position: ArgumentIs(3),
+                   position_span: sp(1, 2),
+                   path: vec![],
+                   span: sp(0, 5),
                    format: FormatSpec {
                        fill: None,
                        align: AlignUnknown,
                        flags: 0,
                        precision: CountImplied,
                        width: CountImplied,
+                       precision_span: None,
+                       width_span: None,
                        ty: "a",
+                       ty_span: Some(sp(3, 4)),
+                       fallback: None,
+                       fallback_span: None,
                    },
                })]);
     }
@@ -564,37 +829,61 @@ mod tests {
         same("{3:>}",
              &[NextArgument(Argument {
                    position: ArgumentIs(3),
+                   position_span: sp(1, 2),
+                   path: vec![],
+                   span: sp(0, 5),
                    format: FormatSpec {
                        fill: None,
                        align: AlignRight,
                        flags: 0,
                        precision: CountImplied,
                        width: CountImplied,
+                       precision_span: None,
+                       width_span: None,
                        ty: "",
+                       ty_span: None,
+                       fallback: None,
+                       fallback_span: None,
                    },
                })]);
         same("{3:0<}",
              &[NextArgument(Argument {
                    position: ArgumentIs(3),
+                   position_span: sp(1, 2),
+                   path: vec![],
+                   span: sp(0, 6),
                    format: FormatSpec {
                        fill: Some('0'),
                        align: AlignLeft,
                        flags: 0,
                        precision: CountImplied,
                        width: CountImplied,
+                       precision_span: None,
+                       width_span: None,
                        ty: "",
+                       ty_span: None,
+                       fallback: None,
+                       fallback_span: None,
                    },
                })]);
         same("{3:*<abcd}",
              &[NextArgument(Argument {
                    position: ArgumentIs(3),
+                   position_span: sp(1, 2),
+                   path: vec![],
+                   span: sp(0, 10),
                    format: FormatSpec {
                        fill: Some('*'),
                        align: AlignLeft,
                        flags: 0,
                        precision: CountImplied,
                        width: CountImplied,
+                       precision_span: None,
+                       width_span: None,
                        ty: "abcd",
+                       ty_span: Some(sp(5, 9)),
+                       fallback: None,
+                       fallback_span: None,
                    },
                })]);
     }
@@ -603,61 +892,101 @@ mod tests {
         same("{:10s}",
              &[NextArgument(Argument {
                    position: ArgumentIs(0),
+                   position_span: sp(1, 1),
+                   path: vec![],
+                   span: sp(0, 6),
                    format: FormatSpec {
                        fill: None,
                        align: AlignUnknown,
                        flags: 0,
                        precision: CountImplied,
                        width: CountIs(10),
+                       precision_span: None,
+                       width_span: Some(sp(2, 4)),
                        ty: "s",
+                       ty_span: Some(sp(4, 5)),
+                       fallback: None,
+                       fallback_span: None,
                    },
                })]);
         same("{:10$.10s}",
              &[NextArgument(Argument {
                    position: ArgumentIs(0),
+                   position_span: sp(1, 1),
+                   path: vec![],
+                   span: sp(0, 10),
                    format: FormatSpec {
                        fill: None,
                        align: AlignUnknown,
                        flags: 0,
                        precision: CountIs(10),
                        width: CountIsParam(10),
+                       precision_span: Some(sp(5, 8)),
+                       width_span: Some(sp(2, 5)),
                        ty: "s",
+                       ty_span: Some(sp(8, 9)),
+                       fallback: None,
+                       fallback_span: None,
                    },
                })]);
         same("{:.*s}",
              &[NextArgument(Argument {
                    position: ArgumentIs(1),
+                   position_span: sp(1, 1),
+                   path: vec![],
+                   span: sp(0, 6),
                    format: FormatSpec {
                        fill: None,
                        align: AlignUnknown,
                        flags: 0,
                        precision: CountIsParam(0),
                        width: CountImplied,
+                       precision_span: Some(sp(2, 4)),
+                       width_span: None,
                        ty: "s",
+                       ty_span: Some(sp(4, 5)),
+                       fallback: None,
+                       fallback_span: None,
                    },
                })]);
         same("{:.10$s}",
              &[NextArgument(Argument {
                    position: ArgumentIs(0),
+                   position_span: sp(1, 1),
+                   path: vec![],
+                   span: sp(0, 8),
                    format: FormatSpec {
                        fill: None,
                        align: AlignUnknown,
                        flags: 0,
                        precision: CountIsParam(10),
                        width: CountImplied,
+                       precision_span: Some(sp(2, 6)),
+                       width_span: None,
                        ty: "s",
+                       ty_span: Some(sp(6, 7)),
+                       fallback: None,
+                       fallback_span: None,
                    },
                })]);
         same("{:a$.b$s}",
              &[NextArgument(Argument {
                    position: ArgumentIs(0),
+                   position_span: sp(1, 1),
+                   path: vec![],
+                   span: sp(0, 9),
                    format: FormatSpec {
                        fill: None,
                        align: AlignUnknown,
                        flags: 0,
                        precision: CountIsName("b"),
                        width: CountIsName("a"),
+                       precision_span: Some(sp(4, 7)),
+                       width_span: Some(sp(2, 4)),
                        ty: "s",
+                       ty_span: Some(sp(7, 8)),
+                       fallback: None,
+                       fallback_span: None,
                    },
                })]);
     }
@@ -666,25 +995,41 @@ mod tests {
         same("{:-}",
              &[NextArgument(Argument {
                    position: ArgumentIs(0),
+                   position_span: sp(1, 1),
+                   path: vec![],
+                   span: sp(0, 4),
                    format: FormatSpec {
                        fill: None,
                        align: AlignUnknown,
                        flags: (1 << FlagSignMinus as u32),
                        precision: CountImplied,
                        width: CountImplied,
+                       precision_span: None,
+                       width_span: None,
                        ty: "",
+                       ty_span: None,
+                       fallback: None,
+                       fallback_span: None,
                    },
                })]);
         same("{:+#}",
              &[NextArgument(Argument {
                    position: ArgumentIs(0),
+                   position_span: sp(1, 1),
+                   path: vec![],
+                   span: sp(0, 5),
                    format: FormatSpec {
                        fill: None,
                        align: AlignUnknown,
                        flags: (1 << FlagSignPlus as u32) | (1 << FlagAlternate as u32),
                        precision: CountImplied,
                        width: CountImplied,
+                       precision_span: None,
+                       width_span: None,
                        ty: "",
+                       ty_span: None,
+                       fallback: None,
+                       fallback_span: None,
                    },
                })]);
     }
@@ -694,15 +1039,165 @@ mod tests {
              &[String("abcd "),
                NextArgument(Argument {
                    position: ArgumentIs(3),
+                   position_span: sp(6, 7),
+                   path: vec![],
+                   span: sp(5, 10),
                    format: FormatSpec {
                        fill: None,
                        align: AlignUnknown,
                        flags: 0,
                        precision: CountImplied,
                        width: CountImplied,
+                       precision_span: None,
+                       width_span: None,
                        ty: "a",
+                       ty_span: Some(sp(8, 9)),
+                       fallback: None,
+                       fallback_span: None,
                    },
                }),
                String(" efg")]);
     }
+    #[test]
+    fn format_path() {
+        same("{a.b.0:x}",
+             &[NextArgument(Argument {
+                   position: ArgumentNamed("a"),
+                   position_span: sp(1, 2),
+                   path: vec![Segment { access: Field("b"), span: sp(2, 4) },
+                              Segment { access: Field("0"), span: sp(4, 6) }],
+                   span: sp(0, 9),
+                   format: FormatSpec {
+                       fill: None,
+                       align: AlignUnknown,
+                       flags: 0,
+                       precision: CountImplied,
+                       width: CountImplied,
+                       precision_span: None,
+                       width_span: None,
+                       ty: "x",
+                       ty_span: Some(sp(7, 8)),
+                       fallback: None,
+                       fallback_span: None,
+                   },
+               })]);
+    }
+    #[test]
+    fn format_hyphenated_names() {
+        same("{user-name.first-name:>max-w$}",
+             &[NextArgument(Argument {
+                   position: ArgumentNamed("user-name"),
+                   position_span: sp(1, 10),
+                   path: vec![Segment { access: Field("first-name"), span: sp(10, 21) }],
+                   span: sp(0, 30),
+                   format: FormatSpec {
+                       align: AlignRight,
+                       width: CountIsName("max-w"),
+                       width_span: Some(sp(23, 29)),
+                       ..fmtdflt()
+                   },
+               })]);
+        musterr("{a-}");
+        musterr("{a--b}");
+        musterr("{a-.b}");
+    }
+    #[test]
+    fn format_elements() {
+        same("{0[-1][\"k\"]}",
+             &[NextArgument(Argument {
+                   position: ArgumentIs(0),
+                   position_span: sp(1, 2),
+                   path: vec![Segment { access: Index(-1), span: sp(2, 6) },
+                              Segment { access: Key("k"), span: sp(6, 11) }],
+                   span: sp(0, 12),
+                   format: fmtdflt(),
+               })]);
+    }
+    #[test]
+    fn format_fallback() {
+        same("{a|n/a}",
+             &[NextArgument(Argument {
+                   position: ArgumentNamed("a"),
+                   position_span: sp(1, 2),
+                   path: vec![],
+                   span: sp(0, 7),
+                   format: FormatSpec {
+                       fallback: Some("n/a"),
+                       fallback_span: Some(sp(2, 6)),
+                       ..fmtdflt()
+                   },
+               })]);
+        same("{|.* x}",
+             &[NextArgument(Argument {
+                   position: ArgumentIs(0),
+                   position_span: sp(1, 1),
+                   path: vec![],
+                   span: sp(0, 7),
+                   format: FormatSpec {
+                       fallback: Some(".* x"),
+                       fallback_span: Some(sp(1, 6)),
+                       ..fmtdflt()
+                   },
+               })]);
+        same("{n:-5}",
+             &[NextArgument(Argument {
+                   position: ArgumentNamed("n"),
+                   position_span: sp(1, 2),
+                   path: vec![],
+                   span: sp(0, 6),
+                   format: FormatSpec {
+                       flags: (1 << FlagSignMinus as u32),
+                       width: CountIs(5),
+                       width_span: Some(sp(4, 5)),
+                       ..fmtdflt()
+                   },
+               })]);
+        same("{n|0}",
+             &[NextArgument(Argument {
+                   position: ArgumentNamed("n"),
+                   position_span: sp(1, 2),
+                   path: vec![],
+                   span: sp(0, 5),
+                   format: FormatSpec {
+                       fallback: Some("0"),
+                       fallback_span: Some(sp(2, 4)),
+                       ..fmtdflt()
+                   },
+               })]);
+        same("{n|-:>w$}",
+             &[NextArgument(Argument {
+                   position: ArgumentNamed("n"),
+                   position_span: sp(1, 2),
+                   path: vec![],
+                   span: sp(0, 9),
+                   format: FormatSpec {
+                       fallback: Some("-"),
+                       fallback_span: Some(sp(2, 4)),
+                       align: AlignRight,
+                       width: CountIsName("w"),
+                       width_span: Some(sp(6, 8)),
+                       ..fmtdflt()
+                   },
+               })]);
+        musterr("{n:x|0}");
+        musterr("{n|12:00:00}");
+    }
+    #[test]
+    fn invalid_path() {
+        musterr("{a.}");
+        musterr("{.a}");
+        musterr("{a[}");
+        musterr("{a[0}");
+        musterr("{a[\"k]}");
+        assert_eq!(errspans("{0.}"), [sp(2, 3)]);
+        assert_eq!(errspans("{0[x]}"), [sp(2, 3), sp(3, 4), sp(5, 6)]);
+    }
+
+    #[test]
+    fn error_spans() {
+        assert_eq!(errspans("ab}"), [sp(2, 3)]);
+        assert_eq!(errspans("a{3a}"), [sp(3, 4), sp(4, 5)]);
+        assert_eq!(errspans("a{:x"), [sp(1, 4)]);
+        assert_eq!(errspans("{0é}"), [sp(2, 4), sp(4, 5)]);
+    }
 }
@@ -0,0 +1,312 @@
+//! Indexing into sequences and maps, as in `{items[0]}` or `{headers["Host"]}`,
+//! and reaching the contents of optional values.
+use std::mem;
+use std::borrow::Borrow;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{BuildHasher, Hash};
+
+use erase::{erase, Opaque, Probe};
+use Traits;
+
+/// Types whose elements may be reached by a signed index, counting from the
+/// end if negative.
+#[doc(hidden)]
+pub trait Sequence {
+    type Element;
+    fn as_slice(&self) -> &[Self::Element];
+}
+
+impl<E> Sequence for [E] {
+    type Element = E;
+    #[inline]
+    fn as_slice(&self) -> &[E] { self }
+}
+
+impl<E> Sequence for Vec<E> {
+    type Element = E;
+    #[inline]
+    fn as_slice(&self) -> &[E] { self }
+}
+
+impl<E, const N: usize> Sequence for [E; N] {
+    type Element = E;
+    #[inline]
+    fn as_slice(&self) -> &[E] { self }
+}
+
+impl<S: Sequence + ?Sized> Sequence for &S {
+    type Element = S::Element;
+    #[inline]
+    fn as_slice(&self) -> &[S::Element] { (**self).as_slice() }
+}
+
+/// Types whose elements may be reached by a string key.
+#[doc(hidden)]
+pub trait Map {
+    type Element;
+    fn lookup(&self, key: &str) -> Option<&Self::Element>;
+}
+
+impl<K: Borrow<str> + Hash + Eq, V, S: BuildHasher> Map for HashMap<K, V, S> {
+    type Element = V;
+    #[inline]
+    fn lookup(&self, key: &str) -> Option<&V> { self.get(key) }
+}
+
+impl<K: Borrow<str> + Ord, V> Map for BTreeMap<K, V> {
+    type Element = V;
+    #[inline]
+    fn lookup(&self, key: &str) -> Option<&V> { self.get(key) }
+}
+
+impl<M: Map + ?Sized> Map for &M {
+    type Element = M::Element;
+    #[inline]
+    fn lookup(&self, key: &str) -> Option<&M::Element> { (**self).lookup(key) }
+}
+
+// Marker for the sequence entry of a `Probe`.
+#[doc(hidden)]
+pub struct Sequenced;
+
+// Marker for the map entry of a `Probe`.
+#[doc(hidden)]
+pub struct Keyed;
+
+// Marker for the optional entry of a `Probe`.
+#[doc(hidden)]
+pub struct Optional;
+
+// Marker for a `Probe` of the element type of a sequence or map.
+#[doc(hidden)]
+pub struct Element;
+
+/// Builds the `Traits` table of an element type, given a probe of it.
+///
+/// The `rt_` macros can only probe a type where it is concrete, so they
+/// supply a closure which probes the element type once the probe of the
+/// sequence or map has named it.
+type ElementTraits<E> = fn(&Probe<Element, E>) -> Traits<Opaque>;
+
+/// How the elements of a sequence or map, or the contents of an `Option`,
+/// are reached.
+#[doc(hidden)]
+pub enum Lookup<T> {
+    Index(fn(&T, isize) -> Option<&Opaque>),
+    Key(for<'a> fn(&'a T, &str) -> Option<&'a Opaque>),
+    Optional(fn(&T) -> Option<&Opaque>),
+}
+
+/// The elements of a sequence or map, or the contents of an `Option`, and
+/// the formatting traits they support.
+#[doc(hidden)]
+pub struct Elements<T> {
+    pub lookup: Lookup<T>,
+    traits: ElementTraits<Opaque>,
+}
+
+impl<T> Copy for Lookup<T> {}
+impl<T> Clone for Lookup<T> {
+    fn clone(&self) -> Self { *self }
+}
+
+impl<T> Copy for Elements<T> {}
+impl<T> Clone for Elements<T> {
+    fn clone(&self) -> Self { *self }
+}
+
+impl<T> Elements<T> {
+    #[inline]
+    fn new<E>(lookup: Lookup<T>, traits: ElementTraits<E>) -> Self {
+        Elements {
+            lookup: lookup,
+            // The probe is a zero-sized marker, so the pointee type of its
+            // reference makes no difference to the function's ABI.
+            traits: unsafe { mem::transmute::<ElementTraits<E>, ElementTraits<Opaque>>(traits) },
+        }
+    }
+
+    /// The formatting traits the elements support.
+    #[inline]
+    pub fn traits(&self) -> Traits<Opaque> {
+        (self.traits)(&Probe::new())
+    }
+
+    #[inline]
+    pub fn erase(self) -> Elements<Opaque> {
+        unsafe {
+            Elements {
+                lookup: match self.lookup {
+                    Lookup::Index(f) => Lookup::Index(mem::transmute::<
+                        fn(&T, isize) -> Option<&Opaque>,
+                        fn(&Opaque, isize) -> Option<&Opaque>
+                    >(f)),
+                    Lookup::Key(f) => Lookup::Key(mem::transmute::<
+                        for<'a> fn(&'a T, &str) -> Option<&'a Opaque>,
+                        for<'a> fn(&'a Opaque, &str) -> Option<&'a Opaque>
+                    >(f)),
+                    Lookup::Optional(f) => Lookup::Optional(mem::transmute::<
+                        fn(&T) -> Option<&Opaque>,
+                        fn(&Opaque) -> Option<&Opaque>
+                    >(f)),
+                },
+                traits: self.traits,
+            }
+        }
+    }
+}
+
+fn index<S: Sequence>(sequence: &S, index: isize) -> Option<&Opaque> {
+    let slice = sequence.as_slice();
+    let index = if index < 0 {
+        slice.len().checked_sub(index.unsigned_abs())?
+    } else {
+        index as usize
+    };
+    slice.get(index).map(|value| unsafe { erase(value) })
+}
+
+fn key<'a, M: Map>(map: &'a M, key: &str) -> Option<&'a Opaque> {
+    map.lookup(key).map(|value| unsafe { erase(value) })
+}
+
+fn contents<E>(option: &Option<E>) -> Option<&Opaque> {
+    option.as_ref().map(|value| unsafe { erase(value) })
+}
+
+#[doc(hidden)]
+pub trait HasSequence<T> {
+    type Element;
+    fn sequence(&self, traits: ElementTraits<Self::Element>) -> Option<Elements<T>>;
+}
+
+#[doc(hidden)]
+pub trait LacksSequence<T> {
+    fn sequence(&self, traits: ElementTraits<Opaque>) -> Option<Elements<T>>;
+}
+
+impl<T: Sequence> HasSequence<T> for Probe<Sequenced, T> {
+    type Element = T::Element;
+    #[inline]
+    fn sequence(&self, traits: ElementTraits<T::Element>) -> Option<Elements<T>> {
+        Some(Elements::new(Lookup::Index(index::<T>), traits))
+    }
+}
+
+impl<T> LacksSequence<T> for &Probe<Sequenced, T> {
+    #[inline]
+    fn sequence(&self, _: ElementTraits<Opaque>) -> Option<Elements<T>> { None }
+}
+
+#[doc(hidden)]
+pub trait HasMap<T> {
+    type Element;
+    fn map(&self, traits: ElementTraits<Self::Element>) -> Option<Elements<T>>;
+}
+
+#[doc(hidden)]
+pub trait LacksMap<T> {
+    fn map(&self, traits: ElementTraits<Opaque>) -> Option<Elements<T>>;
+}
+
+impl<T: Map> HasMap<T> for Probe<Keyed, T> {
+    type Element = T::Element;
+    #[inline]
+    fn map(&self, traits: ElementTraits<T::Element>) -> Option<Elements<T>> {
+        Some(Elements::new(Lookup::Key(key::<T>), traits))
+    }
+}
+
+impl<T> LacksMap<T> for &Probe<Keyed, T> {
+    #[inline]
+    fn map(&self, _: ElementTraits<Opaque>) -> Option<Elements<T>> { None }
+}
+
+#[doc(hidden)]
+pub trait HasOption<T> {
+    type Element;
+    fn option(&self, traits: ElementTraits<Self::Element>) -> Option<Elements<T>>;
+}
+
+#[doc(hidden)]
+pub trait LacksOption<T> {
+    fn option(&self, traits: ElementTraits<Opaque>) -> Option<Elements<T>>;
+}
+
+impl<E> HasOption<Option<E>> for Probe<Optional, Option<E>> {
+    type Element = E;
+    #[inline]
+    fn option(&self, traits: ElementTraits<E>) -> Option<Elements<Option<E>>> {
+        Some(Elements::new(Lookup::Optional(contents::<E>), traits))
+    }
+}
+
+impl<T> LacksOption<T> for &Probe<Optional, T> {
+    #[inline]
+    fn option(&self, _: ElementTraits<Opaque>) -> Option<Elements<T>> { None }
+}
+
+#[cfg(feature = "nightly")]
+trait SequenceOf: Sized {
+    fn sequence() -> Option<Elements<Self>>;
+}
+#[cfg(feature = "nightly")]
+impl<T> SequenceOf for T {
+    #[inline]
+    default fn sequence() -> Option<Elements<Self>> { None }
+}
+#[cfg(feature = "nightly")]
+impl<T: Sequence> SequenceOf for T {
+    #[inline]
+    fn sequence() -> Option<Elements<Self>> {
+        Some(Elements::new(Lookup::Index(index::<T>), detect::<T::Element>))
+    }
+}
+
+#[cfg(feature = "nightly")]
+trait MapOf: Sized {
+    fn map() -> Option<Elements<Self>>;
+}
+#[cfg(feature = "nightly")]
+impl<T> MapOf for T {
+    #[inline]
+    default fn map() -> Option<Elements<Self>> { None }
+}
+#[cfg(feature = "nightly")]
+impl<T: Map> MapOf for T {
+    #[inline]
+    fn map() -> Option<Elements<Self>> {
+        Some(Elements::new(Lookup::Key(key::<T>), detect::<T::Element>))
+    }
+}
+
+#[cfg(feature = "nightly")]
+trait OptionOf: Sized {
+    fn option() -> Option<Elements<Self>>;
+}
+#[cfg(feature = "nightly")]
+impl<T> OptionOf for T {
+    #[inline]
+    default fn option() -> Option<Elements<Self>> { None }
+}
+#[cfg(feature = "nightly")]
+impl<E> OptionOf for Option<E> {
+    #[inline]
+    fn option() -> Option<Elements<Self>> {
+        Some(Elements::new(Lookup::Optional(contents::<E>), detect::<E>))
+    }
+}
+
+/// Detect the elements of a sequence or map, or the contents of an
+/// `Option`, through specialization.
+#[cfg(feature = "nightly")]
+pub fn detect_elements<T>() -> Option<Elements<T>> {
+    <T as SequenceOf>::sequence()
+        .or_else(<T as MapOf>::map)
+        .or_else(<T as OptionOf>::option)
+}
+
+#[cfg(feature = "nightly")]
+fn detect<E>(_: &Probe<Element, E>) -> Traits<Opaque> {
+    Traits::<E>::detect().erase()
+}
@@ -14,58 +14,404 @@
 //! that used by `std::fmt`, including support for positional and named
 //! arguments. This crate shells out to the standard library implementations
 //! for as much as possible to ensure feature parity.
-#![feature(fmt_internals)]
-#![feature(unicode_internals)]
-#![feature(specialization)]
-#![feature(print_internals)]
-#![feature(rustc_private)]
+//!
+//! # Formatting traits
+//!
+//! Which formatting traits a parameter supports is recorded in a `Traits`
+//! table. The `rt_` macros and `#[derive(FormatArgs)]` build these tables
+//! automatically for the concrete types involved, and they may also be
+//! registered explicitly with `Param::normal_with` and `Param::named_with`.
+//!
+//! With the `nightly` feature enabled, `Param::normal` and `Param::named`
+//! become available, using specialization to detect the formatting traits of
+//! any type, even in generic code.
+//!
+//! Tuples, arrays, `Vec` and slices implement `FormatArgs`, so that a
+//! `PreparedFormat::<(i32, &str)>` refers to their elements by index. Their
+//! elements must implement `Formattable`, which every type does with the
+//! `nightly` feature, and which is otherwise implemented for the primitive
+//! types, `String` and `&str`. The length of a `Vec` or slice is not known
+//! until there is a value, so any index into one may be prepared, and
+//! formatting a value too short for it returns `Error::IndexOutOfRange`.
+//!
+//! # Paths
+//!
+//! A placeholder may reach into an argument whose type implements
+//! `FormatArgs`, such as one deriving it, by following a path of fields:
+//! `{user.address.city}` or `{0.name}`. Elements of a `Vec`, slice or array
+//! are reached by index, counting from the end if negative, and those of a
+//! string-keyed map by key: `{items[0]}`, `{tags[-1]}` or
+//! `{headers["Host"]}`.
+//!
+//! Each step of the path is checked when the format string is parsed or
+//! prepared, except that whether an index or key is present can only be
+//! known once there is a value. Without the `nightly` feature, the elements
+//! of elements cannot be reached, as in `{matrix[0][1]}`.
+//!
+//! # Optional values
+//!
+//! An `Option` is formatted as its contents if present, and as nothing if
+//! absent, unless it can be formatted itself, as with `{:?}`. A fallback to
+//! format in place of an absent value may be given after `|` in place of the
+//! `:` of a format spec, as in `{nickname|anonymous}`. It runs up to the
+//! closing brace, or to a `:` followed by the options to format the value or
+//! the fallback with, as in `{nickname|anonymous:>12}`, and so cannot itself
+//! contain a `:`. Paths pass through an `Option`
+//! to its contents, so `{user.address.city|unknown}` formats the fallback if
+//! `address` is absent. Without the `nightly` feature, the elements of a
+//! sequence or map are not treated this way.
+//!
+//! # Inspecting format strings
+//!
+//! The `ast` module parses a format string into an owned syntax tree, for
+//! tools which need to examine templates without formatting anything.
+#![allow(clippy::redundant_field_names)]
+#![cfg_attr(feature = "nightly", feature(specialization))]
+#![cfg_attr(feature = "nightly", allow(incomplete_features))]
 
 #[doc(hidden)]
 #[inline]
 pub fn _print(args: Arguments) {
-    std::io::_print(args)
+    use std::io::Write;
+    if let Err(e) = io::stdout().write_fmt(args) {
+        panic!("failed printing to stdout: {}", e);
+    }
 }
 
+pub mod ast;
+mod builtin;
 pub mod codegen;
+mod compat;
+mod diagnostic;
 mod erase;
+mod index;
 mod macros;
+mod render;
+mod schema;
+mod suggest;
 
 // fmt_macros.rs is from rust/src/libfmt_macros/lib.rs
 // copy-pasted rather than externed to avoid dynamically linking libstd
 mod fmt_macros;
 
 use std::io;
-use std::fmt::{self, Arguments, ArgumentV1};
-use std::fmt::rt::v1;
+use std::fmt::{self, Arguments};
 use std::borrow::Cow;
+use std::convert::TryFrom;
 use std::marker::PhantomData;
 
-pub use codegen::FormatArgs;
+use erase::Argument;
+use render::Spec;
+
+pub use codegen::{FormatArgs, Formattable};
+pub use compat::{compare, Difference};
+pub use diagnostic::Diagnostic;
+pub use erase::{Integer, Kind, Traits};
+pub use schema::Schema;
+
+/// A range of bytes within a format string.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Span {
+    /// The byte offset of the start of the range.
+    pub start: usize,
+    /// The byte offset just past the end of the range.
+    pub end: usize,
+}
+
+impl Span {
+    fn from_inner(span: fmt_macros::InnerSpan) -> Span {
+        Span { start: span.start, end: span.end }
+    }
+}
+
+impl ast::Access {
+    fn from_inner(access: &fmt_macros::Access) -> ast::Access {
+        match *access {
+            fmt_macros::Field(field) => ast::Access::Field(field.to_owned()),
+            fmt_macros::Index(index) => ast::Access::Index(index),
+            fmt_macros::Key(key) => ast::Access::Key(key.to_owned()),
+        }
+    }
+}
+
+/// A single error in the syntax of a format string.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SyntaxError {
+    /// A description of the error.
+    pub description: String,
+    /// An optional note elaborating on the error.
+    pub note: Option<String>,
+    /// The portion of the format string the error refers to.
+    pub span: Span,
+}
+
+impl SyntaxError {
+    fn from_inner(error: fmt_macros::ParseError) -> SyntaxError {
+        SyntaxError {
+            description: error.description,
+            note: error.note,
+            span: Span::from_inner(error.span),
+        }
+    }
+}
 
 /// An error during parsing or formatting.
+///
+/// Errors which arise from the format string carry the `Span` of the
+/// offending portion of it. Names may be borrowed from the format string;
+/// use `into_owned` to obtain an `OwnedError` which does not borrow.
 #[derive(Debug)]
 pub enum Error<'a> {
     /// Invalid format string syntax.
-    BadSyntax(Vec<(String, Option<String>)>),
+    BadSyntax(Vec<SyntaxError>),
     /// A format specifier referred to an out-of-range index.
-    BadIndex(usize),
-    /// A format specifier referred to a non-existent name.
-    BadName(&'a str),
-    /// A format specifier referred to a non-existent type.
-    NoSuchFormat(&'a str),
+    BadIndex(usize, Span),
+    /// A format specifier referred to a non-existent name. Similar names
+    /// which do exist are suggested.
+    BadName(Cow<'a, str>, Span, Vec<&'static str>),
+    /// A format specifier referred to a non-existent type. Similar types
+    /// which do exist are suggested.
+    NoSuchFormat(Cow<'a, str>, Span, Vec<&'static str>),
     /// A format specifier's type was not satisfied by its argument.
     UnsatisfiedFormat {
         idx: usize,
         must_implement: &'static str,
+        span: Span,
     },
     /// A parameter was of a type not suitable for use as a count.
-    BadCount(usize),
+    BadCount(usize, Span),
+    /// A path such as `{0.name}` accessed a field of a value whose type does
+    /// not implement `FormatArgs`.
+    NoFields(Span),
+    /// A path such as `{0[1]}` or `{0["key"]}` indexed a value which is not
+    /// a sequence or map respectively.
+    NotIndexable(Span),
+    /// An index referred to by a format string, as in `{items[3]}`, was out
+    /// of range for the sequence being formatted.
+    IndexOutOfRange(isize),
+    /// A key referred to by a format string, as in `{headers["Host"]}`, was
+    /// not present in the map being formatted.
+    MissingKey(Cow<'a, str>),
+    /// A parameter was never referred to by the format string. Only reported
+    /// by strict parsing.
+    UnusedArgument {
+        idx: usize,
+        name: Option<&'static str>,
+    },
+    /// A field referred to by a prepared format string was not present in
+    /// the value being formatted, as when it belongs to another variant of an
+    /// enum.
+    MissingField {
+        idx: usize,
+        name: Option<&'static str>,
+    },
+    /// A field used as a count by a prepared format string had a value which
+    /// does not fit in a `usize`, such as a negative one.
+    CountOutOfRange {
+        idx: usize,
+        name: Option<&'static str>,
+    },
     /// An I/O error from an `rt_write!` or `rt_writeln!` call.
     Io(std::io::Error),
     /// A formatting error from an `rt_write!` or `rt_writeln!` call.
     Fmt(std::fmt::Error),
 }
 
+impl<'a> Error<'a> {
+    /// The portion of the format string this error refers to, if any.
+    ///
+    /// For `BadSyntax`, this is the span of the first syntax error.
+    pub fn span(&self) -> Option<Span> {
+        match *self {
+            Error::BadSyntax(ref errors) => errors.first().map(|e| e.span),
+            Error::BadIndex(_, span) |
+            Error::BadName(_, span, _) |
+            Error::NoSuchFormat(_, span, _) |
+            Error::UnsatisfiedFormat { span, .. } |
+            Error::BadCount(_, span) |
+            Error::NoFields(span) |
+            Error::NotIndexable(span) => Some(span),
+            Error::UnusedArgument { .. } |
+            Error::MissingField { .. } |
+            Error::CountOutOfRange { .. } |
+            Error::IndexOutOfRange(_) |
+            Error::MissingKey(_) |
+            Error::Io(_) |
+            Error::Fmt(_) => None,
+        }
+    }
+
+    /// Names which were suggested in place of an unknown one.
+    pub fn suggestions(&self) -> &[&'static str] {
+        match *self {
+            Error::BadName(_, _, ref suggestions) |
+            Error::NoSuchFormat(_, _, ref suggestions) => suggestions,
+            _ => &[],
+        }
+    }
+
+    /// Write the message for this error, without any suggestions.
+    fn message(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::BadIndex(i, _) => write!(fmt, "index {} out of range", i),
+            Error::BadName(ref n, _, _) => write!(fmt, "unknown name {:?}", n),
+            Error::NoSuchFormat(ref c, _, _) => write!(fmt, "bad formatting specifier {:?}", c),
+            Error::UnsatisfiedFormat { idx, must_implement, .. } =>
+                write!(fmt, "argument {} does not implement {}", idx, must_implement),
+            Error::BadCount(i, _) => write!(fmt, "argument {} cannot be used as a count", i),
+            Error::NoFields(_) => fmt.write_str("value has no fields"),
+            Error::NotIndexable(_) => fmt.write_str("value cannot be indexed this way"),
+            Error::IndexOutOfRange(i) => write!(fmt, "index {} is out of range for this value", i),
+            Error::MissingKey(ref k) => write!(fmt, "key {:?} is not present in this value", k),
+            Error::UnusedArgument { name: Some(name), .. } =>
+                write!(fmt, "argument {:?} is never used", name),
+            Error::UnusedArgument { idx, name: None } => write!(fmt, "argument {} is never used", idx),
+            Error::MissingField { name: Some(name), .. } =>
+                write!(fmt, "field {:?} is not present in this value", name),
+            Error::MissingField { idx, name: None } =>
+                write!(fmt, "field {} is not present in this value", idx),
+            Error::CountOutOfRange { name: Some(name), .. } =>
+                write!(fmt, "field {:?} is out of range for a count", name),
+            Error::CountOutOfRange { idx, name: None } =>
+                write!(fmt, "field {} is out of range for a count", idx),
+            Error::Io(ref e) => fmt::Display::fmt(e, fmt),
+            Error::Fmt(ref e) => fmt::Display::fmt(e, fmt),
+            Error::BadSyntax(ref errors) => {
+                for (i, err) in errors.iter().enumerate() {
+                    if i > 0 {
+                        fmt.write_str("; ")?;
+                    }
+                    fmt.write_str(&err.description)?;
+                    if let Some(ref more) = err.note {
+                        write!(fmt, " ({})", more)?;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Convert this error into one which does not borrow from the format
+    /// string, such as for use with `Box<dyn std::error::Error>`.
+    pub fn into_owned(self) -> OwnedError {
+        match self {
+            Error::BadSyntax(errors) => Error::BadSyntax(errors),
+            Error::BadIndex(i, span) => Error::BadIndex(i, span),
+            Error::BadName(n, span, s) => Error::BadName(Cow::Owned(n.into_owned()), span, s),
+            Error::NoSuchFormat(c, span, s) => Error::NoSuchFormat(Cow::Owned(c.into_owned()), span, s),
+            Error::UnsatisfiedFormat { idx, must_implement, span } =>
+                Error::UnsatisfiedFormat { idx: idx, must_implement: must_implement, span: span },
+            Error::BadCount(i, span) => Error::BadCount(i, span),
+            Error::NoFields(span) => Error::NoFields(span),
+            Error::NotIndexable(span) => Error::NotIndexable(span),
+            Error::IndexOutOfRange(i) => Error::IndexOutOfRange(i),
+            Error::MissingKey(k) => Error::MissingKey(Cow::Owned(k.into_owned())),
+            Error::UnusedArgument { idx, name } => Error::UnusedArgument { idx: idx, name: name },
+            Error::MissingField { idx, name } => Error::MissingField { idx: idx, name: name },
+            Error::CountOutOfRange { idx, name } => Error::CountOutOfRange { idx: idx, name: name },
+            Error::Io(e) => Error::Io(e),
+            Error::Fmt(e) => Error::Fmt(e),
+        }
+    }
+}
+
+/// An `Error` which does not borrow from the format string.
+pub type OwnedError = Error<'static>;
+
+/// A part of a format string which is legal, but likely a mistake.
+///
+/// Warnings which depend on the type of an argument are only produced when
+/// its `Kind` is known.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Warning {
+    /// A precision was given for an integer, which ignores it.
+    IntegerPrecision {
+        idx: usize,
+        span: Span,
+    },
+    /// A `+` sign was requested for a string, which ignores it.
+    TextSign {
+        idx: usize,
+        span: Span,
+    },
+    /// The alternate flag `#` was given for the `Display` of a primitive,
+    /// which ignores it.
+    AlternateDisplay {
+        idx: usize,
+        span: Span,
+    },
+    /// Both zero-padding and a fill character were given, which is
+    /// contradictory: numbers ignore the fill, and other types the `0` flag.
+    ZeroPadFill {
+        idx: usize,
+        span: Span,
+    },
+    /// A fallback was given for an argument which is never absent, as no
+    /// `Option` is passed through to reach it.
+    NeedlessFallback {
+        idx: usize,
+        span: Span,
+    },
+}
+
+/// A use of an argument by a format string.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Usage {
+    /// The index of the argument.
+    pub idx: usize,
+    /// The name of the argument, if it has one.
+    pub name: Option<&'static str>,
+    /// The fields and elements followed from the argument to the value
+    /// used, empty if the argument is used itself.
+    pub path: Vec<ast::Access>,
+    /// How the argument is used.
+    pub role: Role,
+    /// The portion of the format string which refers to the argument.
+    pub span: Span,
+}
+
+/// How an argument is used by a format string.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Role {
+    /// The argument is formatted using the named trait, such as `"Display"`
+    /// or `"LowerHex"`.
+    Format(&'static str),
+    /// The argument is the width of another.
+    Width,
+    /// The argument is the precision of another.
+    Precision,
+}
+
+impl Warning {
+    /// The portion of the format string this warning refers to.
+    pub fn span(&self) -> Span {
+        match *self {
+            Warning::IntegerPrecision { span, .. } |
+            Warning::TextSign { span, .. } |
+            Warning::AlternateDisplay { span, .. } |
+            Warning::ZeroPadFill { span, .. } |
+            Warning::NeedlessFallback { span, .. } => span,
+        }
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Warning::IntegerPrecision { idx, .. } =>
+                write!(fmt, "precision has no effect on integer argument {}", idx),
+            Warning::TextSign { idx, .. } =>
+                write!(fmt, "`+` has no effect on string argument {}", idx),
+            Warning::AlternateDisplay { idx, .. } =>
+                write!(fmt, "`#` has no effect on the `Display` of argument {}", idx),
+            Warning::ZeroPadFill { idx, .. } =>
+                write!(fmt, "fill character and zero-padding both given for argument {}", idx),
+            Warning::NeedlessFallback { idx, .. } =>
+                write!(fmt, "fallback has no effect on argument {}, which is never absent", idx),
+        }
+    }
+}
+
 impl<'a> From<std::io::Error> for Error<'a> {
     fn from(e: std::io::Error) -> Self {
         Error::Io(e)
@@ -79,14 +425,22 @@ impl<'a> From<std::fmt::Error> for Error<'a> {
 }
 
 impl<'a> std::error::Error for Error<'a> {
+    #[allow(deprecated)]
     fn description(&self) -> &str {
         match *self {
             Error::BadSyntax(_) => "bad syntax",
-            Error::BadIndex(_) => "out-of-range index",
-            Error::BadName(_) => "unknown name",
-            Error::NoSuchFormat(_) => "bad formatting specifier",
+            Error::BadIndex(..) => "out-of-range index",
+            Error::BadName(..) => "unknown name",
+            Error::NoSuchFormat(..) => "bad formatting specifier",
             Error::UnsatisfiedFormat{..} => "formatting trait not satisfied",
-            Error::BadCount(_) => "non-integer used as count",
+            Error::BadCount(..) => "non-integer used as count",
+            Error::NoFields(..) => "field of a value without fields",
+            Error::NotIndexable(..) => "index of a value which cannot be indexed",
+            Error::IndexOutOfRange(..) => "index out of range",
+            Error::MissingKey(..) => "missing key",
+            Error::UnusedArgument { .. } => "unused argument",
+            Error::MissingField { .. } => "missing field",
+            Error::CountOutOfRange { .. } => "count out of range",
             Error::Io(ref e) => e.description(),
             Error::Fmt(ref f) => f.description(),
         }
@@ -102,67 +456,137 @@ impl<'a> std::error::Error for Error<'a> {
 
 impl<'a> fmt::Display for Error<'a> {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        match *self {
-            Error::BadIndex(i) => write!(fmt, "index {} out of range", i),
-            Error::BadName(n) => write!(fmt, "unknown name {:?}", n),
-            Error::NoSuchFormat(c) => write!(fmt, "bad formatting specifier {:?}", c),
-            Error::UnsatisfiedFormat { idx, must_implement } =>
-                write!(fmt, "argument {} does not implement {}", idx, must_implement),
-            Error::BadCount(i) => write!(fmt, "argument {} cannot be used as a count", i),
-            Error::Io(ref e) => e.fmt(fmt),
-            Error::Fmt(ref e) => e.fmt(fmt),
-            Error::BadSyntax(ref errors) => {
-                for (i, err) in errors.iter().enumerate() {
-                    if i > 0 {
-                        fmt.write_str("; ")?;
-                    }
-                    fmt.write_str(&err.0)?;
-                    if let Some(ref more) = err.1 {
-                        write!(fmt, " ({})", more)?;
-                    }
-                }
-                Ok(())
-            }
+        self.message(fmt)?;
+        if let Some(help) = suggest::did_you_mean(self.suggestions()) {
+            write!(fmt, " ({})", help)?;
         }
+        Ok(())
     }
 }
 
 /// A type-erased parameter, with an optional name.
 pub struct Param<'a> {
     name: Option<&'static str>,
-    value: &'a dyn erase::Format,
+    value: &'a erase::Opaque,
+    traits: Traits<erase::Opaque>,
     as_usize: Option<usize>,
 }
 
 impl<'a> Param<'a> {
     /// Create a nameless parameter from the given value.
+    ///
+    /// The formatting traits the value supports are detected through
+    /// specialization.
+    #[cfg(feature = "nightly")]
+    #[inline]
     pub fn normal<T>(t: &'a T) -> Param<'a> {
-        use erase::Format;
-        Param {
-            name: None,
-            as_usize: t.as_usize(),
-            value: t,
-        }
+        Param::normal_with(t, Traits::detect())
     }
 
     /// Create a named parameter from the given value.
+    ///
+    /// The formatting traits the value supports are detected through
+    /// specialization.
+    #[cfg(feature = "nightly")]
+    #[inline]
     pub fn named<T>(name: &'static str, t: &'a T) -> Param<'a> {
-        use erase::Format;
+        Param::named_with(name, t, Traits::detect())
+    }
+
+    /// Create a nameless parameter supporting the given formatting traits.
+    #[inline]
+    pub fn normal_with<T>(t: &'a T, traits: Traits<T>) -> Param<'a> {
+        Param::new(None, t, traits)
+    }
+
+    /// Create a named parameter supporting the given formatting traits.
+    #[inline]
+    pub fn named_with<T>(name: &'static str, t: &'a T, traits: Traits<T>) -> Param<'a> {
+        Param::new(Some(name), t, traits)
+    }
+
+    fn new<T>(name: Option<&'static str>, t: &'a T, traits: Traits<T>) -> Param<'a> {
         Param {
-            name: Some(name),
-            as_usize: t.as_usize(),
-            value: t,
+            name: name,
+            as_usize: traits.as_usize(t),
+            value: unsafe { erase::erase(t) },
+            traits: traits.erase(),
         }
     }
 }
 
-enum PreparedArgument<T> {
-    Normal(fn(&T, &mut fmt::Formatter) -> fmt::Result),
-    Usize(fn(&T) -> &usize),
+/// A step along a path such as `{user.name}` or `{items[0]}`, with the
+/// function which takes it from a value of the type it applies to.
+#[derive(Clone)]
+enum Step {
+    /// A child of a type implementing `FormatArgs`, found with its `get`,
+    /// along with the `len` of that type.
+    Field {
+        get: fn(&erase::Opaque, usize) -> Option<&erase::Opaque>,
+        idx: usize,
+        len: usize,
+        name: Option<&'static str>,
+    },
+    /// An element of a sequence.
+    Index(fn(&erase::Opaque, isize) -> Option<&erase::Opaque>, isize),
+    /// An element of a map.
+    Key(for<'a> fn(&'a erase::Opaque, &str) -> Option<&'a erase::Opaque>, String),
+    /// The contents of an `Option`.
+    Unwrap(fn(&erase::Opaque) -> Option<&erase::Opaque>),
+}
+
+impl Step {
+    /// Take this step from a value, returning `None` if it is an `Option`
+    /// which is absent.
+    fn apply<'v, 's>(&self, value: &'v erase::Opaque) -> Result<Option<&'v erase::Opaque>, Error<'s>> {
+        match *self {
+            Step::Field { get, idx, len, name } => match get(value, idx) {
+                Some(child) => Ok(Some(child)),
+                // Past the children the type always has, as with an element
+                // of a `Vec`, the child is out of range rather than missing.
+                None if idx >= len => Err(Error::IndexOutOfRange(isize::try_from(idx).unwrap_or(isize::MAX))),
+                None => Err(Error::MissingField {
+                    idx: idx,
+                    name: name,
+                }),
+            },
+            Step::Index(get, index) => get(value, index).map(Some).ok_or(Error::IndexOutOfRange(index)),
+            Step::Key(get, ref key) => {
+                get(value, key).map(Some).ok_or_else(|| Error::MissingKey(key.clone().into()))
+            }
+            Step::Unwrap(get) => Ok(get(value)),
+        }
+    }
 }
-impl<T> Copy for PreparedArgument<T> {}
-impl<T> Clone for PreparedArgument<T> {
-    fn clone(&self) -> Self { *self }
+
+/// Follow a path from a value, returning `None` if an `Option` along the
+/// way is absent.
+fn walk<'v, 's>(mut value: &'v erase::Opaque, path: &[Step]) -> Result<Option<&'v erase::Opaque>, Error<'s>> {
+    for step in path {
+        value = match step.apply(value)? {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+    }
+    Ok(Some(value))
+}
+
+/// An argument which is formatted in place of its value, if absent.
+fn present_or<'v>(value: Option<&'v erase::Opaque>, func: Func, fallback: Option<&'v str>) -> Argument<'v> {
+    match value {
+        Some(value) => Argument::new(value, func),
+        None => Argument::text(fallback.unwrap_or("")),
+    }
+}
+
+/// An argument of a prepared format string: the index of the child it
+/// refers to, the path from there to the value, how to format it, the
+/// method which computes it from the end of the path, if any, and the text
+/// to format if it is absent.
+#[derive(Clone)]
+enum PreparedArgument {
+    Normal(usize, Vec<Step>, Func, Option<erase::Compute>, Option<String>),
+    Count(usize, fn(&erase::Opaque) -> Option<usize>),
 }
 
 /// A pre-checked format string, ready for values of a specific type to be
@@ -177,11 +601,57 @@ impl<'s, T: FormatArgs> PreparedFormat<'s, T> {
     /// Once the format string has been prepared, formatting individual values
     /// will not require checking the validity of the format string over again.
     #[inline]
-    pub fn prepare(spec: &'s str) -> Result<Self, Error> {
-        parse(spec, &mut DelayedParse::<T>(PhantomData))
+    pub fn prepare(spec: &'s str) -> Result<Self, Error<'s>> {
+        parse(spec, &mut DelayedParse::<T>(PhantomData), false)
             .map(|result| PreparedFormat { inner: result })
     }
 
+    /// Prepare a format string against a formattable type, additionally
+    /// rejecting it if any field of the type goes unused. Children computed
+    /// by methods may go unused.
+    #[inline]
+    pub fn prepare_strict(spec: &'s str) -> Result<Self, Error<'s>> {
+        parse(spec, &mut DelayedParse::<T>(PhantomData), true)
+            .map(|result| PreparedFormat { inner: result })
+    }
+
+    /// Check a format string against a formattable type, reporting every
+    /// error rather than only the first.
+    ///
+    /// Each syntax error is reported as its own `Error::BadSyntax`, and the
+    /// errors are ordered by their position in the format string.
+    #[inline]
+    pub fn validate(spec: &'s str) -> Result<(), Vec<Error<'s>>> {
+        validate(spec, &mut DelayedParse::<T>(PhantomData), false)
+    }
+
+    /// Check a format string as `validate` does, additionally reporting any
+    /// field of the type which goes unused.
+    #[inline]
+    pub fn validate_strict(spec: &'s str) -> Result<(), Vec<Error<'s>>> {
+        validate(spec, &mut DelayedParse::<T>(PhantomData), true)
+    }
+
+    /// The warnings produced while parsing the format string.
+    #[inline]
+    pub fn warnings(&self) -> &[Warning] {
+        &self.inner.warnings
+    }
+
+    /// Every use of an argument by the format string, in order of
+    /// appearance. An argument used more than once appears more than once.
+    #[inline]
+    pub fn usages(&self) -> &[Usage] {
+        &self.inner.usages
+    }
+
+    /// Compare the arguments used by a translation of this format string to
+    /// those used by this one. See `compare` for details.
+    #[inline]
+    pub fn differences(&self, translation: &PreparedFormat<T>) -> Vec<Difference> {
+        compare(self.usages(), translation.usages())
+    }
+
     /// Append a linefeed (`\n`) to the end of this buffer.
     #[inline]
     pub fn newln(&mut self) -> &mut Self {
@@ -190,41 +660,129 @@ impl<'s, T: FormatArgs> PreparedFormat<'s, T> {
     }
 
     /// Call a function accepting `Arguments` with the contents of this buffer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value cannot be formatted for any reason `try_with`
+    /// reports, such as a field of an enum, or an element of a vector or
+    /// slice, which is not present in it. Prefer `try_with` unless the value
+    /// is known to have everything the format string refers to.
     pub fn with<F: FnOnce(Arguments) -> R, R>(&self, t: &T, f: F) -> R {
+        match self.try_with(t, f) {
+            Ok(result) => result,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Call a function accepting `Arguments` with the contents of this
+    /// buffer, or return an `Error::MissingField` if a field the format
+    /// string refers to is not present in the value. Likewise, an index or
+    /// key which is not present, including an element of a vector or slice
+    /// itself, returns `Error::IndexOutOfRange` or
+    /// `Error::MissingKey`, and a count which does not fit in a `usize`
+    /// returns `Error::CountOutOfRange`.
+    pub fn try_with<F: FnOnce(Arguments) -> R, R>(&self, t: &T, f: F) -> Result<R, Error<'s>> {
         let pieces = self.inner.pieces();
-        let args: Vec<ArgumentV1> = self.inner.args.iter().map(|f| match *f {
-            PreparedArgument::Normal(func) => ArgumentV1::new(t, func),
-            PreparedArgument::Usize(func) => ArgumentV1::from_usize(func(t)),
-        }).collect();
-        f(match self.inner.fmt {
-            Some(ref fmt) => Arguments::new_v1_formatted(&pieces, &args, fmt),
-            None => Arguments::new_v1(&pieces, &args),
-        })
+        let mut args = Vec::with_capacity(self.inner.args.len());
+        for arg in &self.inner.args {
+            let idx = match *arg {
+                PreparedArgument::Normal(idx, ..) | PreparedArgument::Count(idx, _) => idx,
+            };
+            let value = match t.get(idx) {
+                Some(value) => value,
+                None if idx >= T::len() => {
+                    let idx = isize::try_from(idx).unwrap_or(isize::MAX);
+                    return Err(Error::IndexOutOfRange(idx));
+                }
+                None => return Err(Error::MissingField {
+                    idx: idx,
+                    name: T::names().get(idx).cloned().filter(|name| !name.is_empty()),
+                }),
+            };
+            args.push(match *arg {
+                PreparedArgument::Normal(_, ref path, func, compute, ref fallback) => {
+                    let fallback = fallback.as_ref().map(|text| &text[..]);
+                    present_or(walk(value, path)?, func, fallback).computed(compute)
+                }
+                PreparedArgument::Count(idx, count) => match count(value) {
+                    Some(count) => Argument::from_count(count),
+                    None => return Err(Error::CountOutOfRange {
+                        idx: idx,
+                        name: T::names().get(idx).cloned().filter(|name| !name.is_empty()),
+                    }),
+                },
+            });
+        }
+        Ok(with_arguments(&pieces, &args, &self.inner.fmt, f))
     }
 
     /// Format the given value to a `String`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value cannot be formatted, as described for `with`.
     #[inline]
     pub fn format(&self, t: &T) -> String {
         self.with(t, ::std::fmt::format)
     }
 
+    /// Format the given value to a `String`, or return an error if it
+    /// cannot be formatted, as described for `try_with`.
+    #[inline]
+    pub fn try_format(&self, t: &T) -> Result<String, Error<'s>> {
+        self.try_with(t, ::std::fmt::format)
+    }
+
     /// Print the given value to standard output.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value cannot be formatted, as described for `with`.
     #[inline]
     pub fn print(&self, t: &T) {
         self.with(t, _print)
     }
 
+    /// Print the given value to standard output, or return an error if it
+    /// cannot be formatted, as described for `try_with`.
+    #[inline]
+    pub fn try_print(&self, t: &T) -> Result<(), Error<'s>> {
+        self.try_with(t, _print)
+    }
+
     /// Write the given value to an `io::Write`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value cannot be formatted, as described for `with`.
     #[inline]
     pub fn write_io<W: io::Write + ?Sized>(&self, t: &T, dest: &mut W) -> io::Result<()> {
         self.with(t, |args| dest.write_fmt(args))
     }
 
+    /// Write the given value to an `io::Write`, or return an error if it
+    /// cannot be formatted, as described for `try_with`, or written.
+    #[inline]
+    pub fn try_write_io<W: io::Write + ?Sized>(&self, t: &T, dest: &mut W) -> Result<(), Error<'s>> {
+        self.try_with(t, |args| dest.write_fmt(args))?.map_err(From::from)
+    }
+
     /// Write the given value to a `fmt::Write`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value cannot be formatted, as described for `with`.
     #[inline]
     pub fn write_fmt<W: fmt::Write + ?Sized>(&self, t: &T, dest: &mut W) -> fmt::Result {
         self.with(t, |args| dest.write_fmt(args))
     }
+
+    /// Write the given value to a `fmt::Write`, or return an error if it
+    /// cannot be formatted, as described for `try_with`, or written.
+    #[inline]
+    pub fn try_write_fmt<W: fmt::Write + ?Sized>(&self, t: &T, dest: &mut W) -> Result<(), Error<'s>> {
+        self.try_with(t, |args| dest.write_fmt(args))?.map_err(From::from)
+    }
 }
 
 impl<'s, T: FormatArgs> Clone for PreparedFormat<'s, T> {
@@ -249,10 +807,48 @@ impl<'s> FormatBuf<'s> {
     /// `rt_format_args!` macro.
     #[inline]
     pub fn new(spec: &'s str, params: &'s [Param<'s>]) -> Result<Self, Error<'s>> {
-        parse(spec, &mut ImmediateParse(params))
+        parse(spec, &mut ImmediateParse(params), false)
             .map(|result| FormatBuf { inner: result })
     }
 
+    /// Construct a new buffer as `new` does, additionally rejecting the
+    /// format string if any of the arguments goes unused.
+    #[inline]
+    pub fn new_strict(spec: &'s str, params: &'s [Param<'s>]) -> Result<Self, Error<'s>> {
+        parse(spec, &mut ImmediateParse(params), true)
+            .map(|result| FormatBuf { inner: result })
+    }
+
+    /// Check a format string against the given arguments, reporting every
+    /// error rather than only the first.
+    ///
+    /// Each syntax error is reported as its own `Error::BadSyntax`, and the
+    /// errors are ordered by their position in the format string.
+    #[inline]
+    pub fn validate(spec: &'s str, params: &'s [Param<'s>]) -> Result<(), Vec<Error<'s>>> {
+        validate(spec, &mut ImmediateParse(params), false)
+    }
+
+    /// Check a format string as `validate` does, additionally reporting any
+    /// of the arguments which goes unused.
+    #[inline]
+    pub fn validate_strict(spec: &'s str, params: &'s [Param<'s>]) -> Result<(), Vec<Error<'s>>> {
+        validate(spec, &mut ImmediateParse(params), true)
+    }
+
+    /// The warnings produced while parsing the format string.
+    #[inline]
+    pub fn warnings(&self) -> &[Warning] {
+        &self.inner.warnings
+    }
+
+    /// Every use of an argument by the format string, in order of
+    /// appearance. An argument used more than once appears more than once.
+    #[inline]
+    pub fn usages(&self) -> &[Usage] {
+        &self.inner.usages
+    }
+
     /// Append a linefeed (`\n`) to the end of this buffer.
     #[inline]
     pub fn newln(&mut self) -> &mut Self {
@@ -263,10 +859,7 @@ impl<'s> FormatBuf<'s> {
     /// Call a function accepting `Arguments` with the contents of this buffer.
     pub fn with<F: FnOnce(Arguments) -> R, R>(&self, f: F) -> R {
         let pieces = self.inner.pieces();
-        f(match self.inner.fmt {
-            Some(ref fmt) => Arguments::new_v1_formatted(&pieces, &self.inner.args, fmt),
-            None => Arguments::new_v1(&pieces, &self.inner.args),
-        })
+        with_arguments(&pieces, &self.inner.args, &self.inner.fmt, f)
     }
 
     /// Format this buffer to a `String`.
@@ -308,40 +901,85 @@ impl<'a> fmt::Debug for FormatBuf<'a> {
     }
 }
 
+fn with_arguments<F, R>(pieces: &[&str], args: &[Argument], fmt: &Option<Vec<Spec>>, f: F) -> R
+    where F: FnOnce(Arguments) -> R
+{
+    f(format_args!("{}", render::Arguments::new(pieces, args, fmt.as_ref().map(|fmt| &fmt[..]))))
+}
+
 trait ParseTarget<'p> {
     type Argument;
     fn validate_name(&mut self, name: &str) -> Option<usize>;
     fn validate_index(&mut self, index: usize) -> bool;
-    fn format<'s>(&mut self, spec: &'s str, idx: usize) -> Result<Self::Argument, Error<'s>>;
+    fn traits(&self, idx: usize) -> Traits<erase::Opaque>;
+    fn compute(&self, _idx: usize) -> Option<erase::Compute> {
+        None
+    }
+    fn format<'s>(&mut self,
+                  idx: usize,
+                  path: Vec<Step>,
+                  func: Func,
+                  compute: Option<erase::Compute>,
+                  fallback: Option<&'p str>)
+        -> Result<Self::Argument, Error<'s>>;
     fn format_usize(&mut self, idx: usize) -> Option<Self::Argument>;
+    fn names(&self) -> Vec<&'static str>;
+    fn len(&self) -> usize;
+    fn name_of(&self, idx: usize) -> Option<&'static str>;
 }
 
+type Func = fn(&erase::Opaque, &mut fmt::Formatter) -> fmt::Result;
+
 struct ImmediateParse<'p>(&'p [Param<'p>]);
 
 impl<'p> ParseTarget<'p> for ImmediateParse<'p> {
-    type Argument = ArgumentV1<'p>;
+    type Argument = Argument<'p>;
 
     fn validate_name(&mut self, name: &str) -> Option<usize> {
-        self.0.iter().position(|p| p.name.map_or(false, |n| n == name))
+        self.0.iter().position(|p| p.name == Some(name))
     }
 
     fn validate_index(&mut self, index: usize) -> bool {
         index < self.0.len()
     }
 
-    fn format<'s>(&mut self, spec: &'s str, idx: usize) -> Result<Self::Argument, Error<'s>> {
-        self.0[idx].value.by_name(spec, idx)
+    fn traits(&self, idx: usize) -> Traits<erase::Opaque> {
+        self.0[idx].traits
+    }
+
+    fn format<'s>(&mut self,
+                  idx: usize,
+                  path: Vec<Step>,
+                  func: Func,
+                  compute: Option<erase::Compute>,
+                  fallback: Option<&'p str>)
+        -> Result<Self::Argument, Error<'s>>
+    {
+        let value = walk(self.0[idx].value, &path)?;
+        Ok(present_or(value, func, fallback).computed(compute))
     }
 
     fn format_usize(&mut self, idx: usize) -> Option<Self::Argument> {
-        self.0[idx].as_usize.as_ref().map(ArgumentV1::from_usize)
+        self.0[idx].as_usize.as_ref().map(Argument::from_usize)
+    }
+
+    fn names(&self) -> Vec<&'static str> {
+        self.0.iter().filter_map(|p| p.name).collect()
+    }
+
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    fn name_of(&self, idx: usize) -> Option<&'static str> {
+        self.0[idx].name
     }
 }
 
 struct DelayedParse<T>(PhantomData<fn(&T)>);
 
 impl<'p, T: FormatArgs> ParseTarget<'p> for DelayedParse<T> {
-    type Argument = PreparedArgument<T>;
+    type Argument = PreparedArgument;
 
     fn validate_name(&mut self, name: &str) -> Option<usize> {
         T::validate_name(name)
@@ -351,19 +989,54 @@ impl<'p, T: FormatArgs> ParseTarget<'p> for DelayedParse<T> {
         T::validate_index(index)
     }
 
-    fn format<'s>(&mut self, spec: &'s str, idx: usize) -> Result<Self::Argument, Error<'s>> {
-        erase::codegen_get_child::<T>(spec, idx).map(PreparedArgument::Normal)
+    fn traits(&self, idx: usize) -> Traits<erase::Opaque> {
+        T::get_child(idx).traits
+    }
+
+    fn compute(&self, idx: usize) -> Option<erase::Compute> {
+        T::get_child(idx).compute
+    }
+
+    fn format<'s>(&mut self,
+                  idx: usize,
+                  path: Vec<Step>,
+                  func: Func,
+                  compute: Option<erase::Compute>,
+                  fallback: Option<&'p str>)
+        -> Result<Self::Argument, Error<'s>>
+    {
+        Ok(PreparedArgument::Normal(idx, path, func, compute, fallback.map(str::to_owned)))
     }
 
     fn format_usize(&mut self, idx: usize) -> Option<Self::Argument> {
-        T::as_usize(idx).map(PreparedArgument::Usize)
+        // The value of a computed child is its parent, which is not the
+        // count its traits describe.
+        let child = T::get_child(idx);
+        if child.compute.is_some() {
+            return None;
+        }
+        child.traits.get_count().map(|count| PreparedArgument::Count(idx, count))
+    }
+
+    fn names(&self) -> Vec<&'static str> {
+        T::names().iter().cloned().filter(|name| !name.is_empty()).collect()
+    }
+
+    fn len(&self) -> usize {
+        T::len()
+    }
+
+    fn name_of(&self, idx: usize) -> Option<&'static str> {
+        T::names().get(idx).cloned().filter(|name| !name.is_empty())
     }
 }
 
 struct Parsed<'s, P: ParseTarget<'s>> {
     pieces: Vec<Cow<'s, str>>,
     args: Vec<P::Argument>,
-    fmt: Option<Vec<v1::Argument>>,
+    fmt: Option<Vec<Spec>>,
+    warnings: Vec<Warning>,
+    usages: Vec<Usage>,
 }
 
 impl<'s, P: ParseTarget<'s>> Clone for Parsed<'s, P>
@@ -374,6 +1047,8 @@ impl<'s, P: ParseTarget<'s>> Clone for Parsed<'s, P>
             pieces: self.pieces.clone(),
             args: self.args.clone(),
             fmt: self.fmt.clone(),
+            warnings: self.warnings.clone(),
+            usages: self.usages.clone(),
         }
     }
 
@@ -381,6 +1056,8 @@ impl<'s, P: ParseTarget<'s>> Clone for Parsed<'s, P>
         self.pieces.clone_from(&source.pieces);
         self.args.clone_from(&source.args);
         self.fmt.clone_from(&source.fmt);
+        self.warnings.clone_from(&source.warnings);
+        self.usages.clone_from(&source.usages);
     }
 }
 
@@ -392,7 +1069,7 @@ impl<'s, P: ParseTarget<'s>> Parsed<'s, P> {
         if self.pieces.len() > len {
             // The final piece is after the final formatting specifier, so
             // it's okay to just add to the end of it.
-            self.pieces.last_mut().unwrap().to_mut().push_str("\n")
+            self.pieces.last_mut().unwrap().to_mut().push('\n')
         } else {
             // The final piece is before the final formatting specifier, so
             // a new piece needs to be added at the end.
@@ -406,49 +1083,103 @@ impl<'s, P: ParseTarget<'s>> Parsed<'s, P> {
     }
 }
 
-fn parse<'s, P: ParseTarget<'s>>(spec: &'s str, target: &mut P)
+fn parse<'s, P: ParseTarget<'s>>(spec: &'s str, target: &mut P, strict: bool)
     -> Result<Parsed<'s, P>, Error<'s>>
 {
-    let mut parser = fmt_macros::Parser::new(spec);
-    let result = inner_parse(&mut parser, target);
-    // Perform a separate check so that syntax errors take priority.
-    if parser.errors.is_empty() {
-        result
+    let (result, syntax, mut errors) = parse_all(spec, target, strict);
+    // Syntax errors take priority, then the first of the others.
+    if !syntax.is_empty() {
+        Err(Error::BadSyntax(syntax))
+    } else if !errors.is_empty() {
+        Err(errors.swap_remove(0))
     } else {
-        Err(Error::BadSyntax(parser.errors))
+        Ok(result)
     }
 }
 
-fn inner_parse<'s, P>(parser: &mut fmt_macros::Parser<'s>, target: &mut P)
-    -> Result<Parsed<'s, P>, Error<'s>>
+fn validate<'s, P: ParseTarget<'s>>(spec: &'s str, target: &mut P, strict: bool)
+    -> Result<(), Vec<Error<'s>>>
+{
+    let (_, syntax, mut errors) = parse_all(spec, target, strict);
+    errors.extend(syntax.into_iter().map(|e| Error::BadSyntax(vec![e])));
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        // Errors without a span, such as unused arguments, come last.
+        errors.sort_by_key(|e| e.span().map_or(usize::MAX, |span| span.start));
+        Err(errors)
+    }
+}
+
+/// Parse a format string, collecting every syntax and other error. If
+/// `strict`, unused arguments are errors too. The result is only meaningful
+/// if there were no errors.
+fn parse_all<'s, P: ParseTarget<'s>>(spec: &'s str, target: &mut P, strict: bool)
+    -> (Parsed<'s, P>, Vec<SyntaxError>, Vec<Error<'s>>)
+{
+    let mut parser = fmt_macros::Parser::new(spec);
+    let mut errors = Vec::new();
+    let mut used = Vec::new();
+    let result = inner_parse(&mut parser, target, &mut errors, &mut used);
+    if strict {
+        // Children computed by methods are there to be used when wanted,
+        // not values the caller supplied, so they may go unused.
+        for idx in 0..target.len() {
+            if !used.contains(&idx) && target.compute(idx).is_none() {
+                errors.push(Error::UnusedArgument {
+                    idx: idx,
+                    name: target.name_of(idx),
+                });
+            }
+        }
+    }
+    let syntax = parser.errors.into_iter().map(SyntaxError::from_inner).collect();
+    (result, syntax, errors)
+}
+
+/// Record the error of a result, if any, and return its value otherwise.
+fn record<'s, T>(errors: &mut Vec<Error<'s>>, result: Result<T, Error<'s>>) -> Option<T> {
+    match result {
+        Ok(value) => Some(value),
+        Err(err) => {
+            errors.push(err);
+            None
+        }
+    }
+}
+
+fn inner_parse<'s, P>(parser: &mut fmt_macros::Parser<'s>,
+                      target: &mut P,
+                      errors: &mut Vec<Error<'s>>,
+                      used: &mut Vec<usize>)
+    -> Parsed<'s, P>
     where P: ParseTarget<'s>
 {
     use fmt_macros as p;
 
-    const DEFAULT_FILL: char = ' ';
     const DEFAULT_KEY: p::FormatSpec = p::FormatSpec {
         fill: None,
         align: p::AlignUnknown,
         flags: 0,
         precision: p::CountImplied,
         width: p::CountImplied,
+        precision_span: None,
+        width_span: None,
         ty: "",
-    };
-    const DEFAULT_VALUE: v1::FormatSpec = v1::FormatSpec {
-        fill: DEFAULT_FILL,
-        align: v1::Alignment::Unknown,
-        flags: 0,
-        precision: v1::Count::Implied,
-        width: v1::Count::Implied,
+        ty_span: None,
+        fallback: None,
+        fallback_span: None,
     };
 
     let mut pieces = Vec::new();
     let mut args = Vec::new();
     let mut fmt = None;
     let mut fmt_len = 0;
+    let mut warnings = Vec::new();
+    let mut usages = Vec::new();
 
     let mut str_accum: Cow<str> = "".into();
-    while let Some(piece) = parser.next() {
+    for piece in parser.by_ref() {
         match piece {
             p::Piece::String(text) => {
                 // append string to accumulator
@@ -470,79 +1201,140 @@ fn inner_parse<'s, P>(parser: &mut fmt_macros::Parser<'s>, target: &mut P)
                 // flush accumulator always
                 pieces.push(std::mem::replace(&mut str_accum, "".into()));
 
+                // Errors point at the relevant part of the argument, or at
+                // the whole argument if that part was implicit.
+                let whole_span = Span::from_inner(arg.span);
+                let span_or_whole = |span: Option<p::InnerSpan>| {
+                    span.map_or(whole_span, Span::from_inner)
+                };
+
                 // convert the argument
-                let idx = match arg.position {
+                let position_span = match arg.position_span {
+                    span if span.start == span.end => whole_span,
+                    span => Span::from_inner(span),
+                };
+                let idx = record(errors, match arg.position {
                     p::Position::ArgumentIs(idx) => {
-                        if !target.validate_index(idx) {
-                            return Err(Error::BadIndex(idx))
+                        if target.validate_index(idx) {
+                            Ok(idx)
+                        } else {
+                            Err(Error::BadIndex(idx, position_span))
                         }
-                        idx
                     }
                     p::Position::ArgumentNamed(name) => {
-                        match target.validate_name(name) {
-                            Some(idx) => idx,
-                            None => return Err(Error::BadName(name))
+                        target.validate_name(name).ok_or_else(|| {
+                            let suggestions = suggest::similar(name, &target.names());
+                            Error::BadName(name.into(), position_span, suggestions)
+                        })
+                    }
+                });
+                if let Some(idx) = idx {
+                    used.push(idx);
+                }
+                let ty_span = span_or_whole(arg.format.ty_span);
+                let argument = idx.and_then(|idx| {
+                    // The traits and kind are those of the value at the end
+                    // of the path, if there is one.
+                    let root = (target.traits(idx), target.compute(idx));
+                    let ((traits, compute), mut path) = record(errors, follow(root, &arg.path))?;
+                    // An `Option` is formatted by its contents when given a
+                    // fallback, or when it cannot be formatted itself.
+                    let fallback = arg.format.fallback;
+                    let traits = match unwrap(&traits) {
+                        Some((step, inner)) if fallback.is_some() ||
+                            traits.by_name(arg.format.ty, idx, ty_span).is_err() =>
+                        {
+                            path.push(step);
+                            inner
                         }
+                        _ => traits,
+                    };
+                    let func = record(errors, traits.by_name(arg.format.ty, idx, ty_span))?;
+                    if fallback.is_some() && !path.iter().any(|step| matches!(*step, Step::Unwrap(_))) {
+                        warnings.push(Warning::NeedlessFallback {
+                            idx: idx,
+                            span: span_or_whole(arg.format.fallback_span),
+                        });
                     }
-                };
-                let argument_pos = push_arg(target.format(arg.format.ty, idx)?);
+                    let argument = record(errors, target.format(idx, path, func, compute, fallback))?;
+                    lint(&arg.format, idx, traits.kind(), whole_span, &mut warnings);
+                    usages.push(Usage {
+                        idx: idx,
+                        name: target.name_of(idx),
+                        path: arg.path.iter().map(|segment| ast::Access::from_inner(&segment.access)).collect(),
+                        role: Role::Format(erase::trait_name(arg.format.ty).unwrap_or("")),
+                        span: position_span,
+                    });
+                    Some(argument)
+                });
+                let argument_pos = argument.map(&mut push_arg);
 
                 // convert the format spec
-                let mut convert_count = |c| -> Result<v1::Count, Error<'s>> {
-                    Ok(match c {
-                        p::CountIs(val) => v1::Count::Is(val),
-                        p::CountIsName(name) => {
-                            let idx = match target.validate_name(name) {
-                                Some(idx) => idx,
-                                None => return Err(Error::BadName(name))
-                            };
-                            v1::Count::Param(push_arg(match target.format_usize(idx) {
-                                Some(arg) => arg,
-                                None => return Err(Error::BadCount(idx))
-                            }))
-                        }
+                let mut convert_count = |c, span, role| -> Result<render::Count, Error<'s>> {
+                    let idx = match c {
+                        p::CountIs(val) => return Ok(render::Count::Is(val)),
+                        p::CountIsName(name) => match target.validate_name(name) {
+                            Some(idx) => idx,
+                            None => {
+                                let suggestions = suggest::similar(name, &target.names());
+                                return Err(Error::BadName(name.into(), span, suggestions))
+                            }
+                        },
                         p::CountIsParam(idx) => {
                             if !target.validate_index(idx) {
-                                return Err(Error::BadIndex(idx))
+                                return Err(Error::BadIndex(idx, span))
                             }
-                            v1::Count::Param(push_arg(match target.format_usize(idx) {
-                                Some(arg) => arg,
-                                None => return Err(Error::BadCount(idx))
-                            }))
+                            idx
                         },
-                        p::CountImplied => v1::Count::Implied,
-                    })
+                        p::CountImplied => return Ok(render::Count::Implied),
+                    };
+                    used.push(idx);
+                    let arg = match target.format_usize(idx) {
+                        Some(arg) => arg,
+                        None => return Err(Error::BadCount(idx, span))
+                    };
+                    usages.push(Usage {
+                        idx: idx,
+                        name: target.name_of(idx),
+                        path: Vec::new(),
+                        role: role,
+                        span: span,
+                    });
+                    Ok(render::Count::Param(push_arg(arg)))
                 };
 
-                // If specs were implicit but this is non-default, fill in the
-                // previously-implicit values.
-                if fmt.is_none() && (arg.format != DEFAULT_KEY || argument_pos != fmt_len) {
-                    fmt = Some((0..fmt_len).map(|i| v1::Argument {
-                        position: v1::Position::At(i),
-                        format: DEFAULT_VALUE,
-                    }).collect::<Vec<_>>());
-                }
-
-                // If specs are currently explicit, push this spec.
-                if let Some(fmt) = fmt.as_mut() {
-                    let spec = v1::FormatSpec {
-                        fill: arg.format.fill.unwrap_or(DEFAULT_FILL),
-                        flags: arg.format.flags,
-                        align: match arg.format.align {
-                            p::AlignLeft => v1::Alignment::Left,
-                            p::AlignRight => v1::Alignment::Right,
-                            p::AlignCenter => v1::Alignment::Center,
-                            p::AlignUnknown => v1::Alignment::Unknown,
-                        },
-                        precision: convert_count(arg.format.precision)?,
-                        width: convert_count(arg.format.width)?,
-                    };
+                let width = convert_count(arg.format.width,
+                                          span_or_whole(arg.format.width_span),
+                                          Role::Width);
+                let width = record(errors, width);
+                let precision = convert_count(arg.format.precision,
+                                              span_or_whole(arg.format.precision_span),
+                                              Role::Precision);
+                let precision = record(errors, precision);
+
+                if let (Some(argument_pos), Some(precision), Some(width)) = (argument_pos, precision, width) {
+                    // If specs were implicit but this is non-default, fill in
+                    // the previously-implicit values.
+                    if fmt.is_none() && (arg.format != DEFAULT_KEY || argument_pos != fmt_len) {
+                        fmt = Some((0..fmt_len).map(Spec::plain).collect::<Vec<_>>());
+                    }
 
-                    // push the format spec and argument value
-                    fmt.push(v1::Argument {
-                        position: v1::Position::At(argument_pos),
-                        format: spec,
-                    })
+                    // If specs are currently explicit, push this spec.
+                    if let Some(fmt) = fmt.as_mut() {
+                        fmt.push(Spec {
+                            arg: argument_pos,
+                            fill: arg.format.fill.unwrap_or(' '),
+                            flags: arg.format.flags,
+                            align: match arg.format.align {
+                                p::AlignLeft => render::Alignment::Left,
+                                p::AlignRight => render::Alignment::Right,
+                                p::AlignCenter => render::Alignment::Center,
+                                p::AlignUnknown => render::Alignment::Unknown,
+                            },
+                            precision: precision,
+                            width: width,
+                        })
+                    }
                 }
 
                 fmt_len += 1;
@@ -554,9 +1346,115 @@ fn inner_parse<'s, P>(parser: &mut fmt_macros::Parser<'s>, target: &mut P)
         pieces.push(str_accum);
     }
 
-    Ok(Parsed {
+    Parsed {
         pieces: pieces,
         args: args,
         fmt: fmt,
-    })
+        warnings: warnings,
+        usages: usages,
+    }
+}
+
+/// The traits of a value and the method which computes it, if any.
+type Target = (Traits<erase::Opaque>, Option<erase::Compute>);
+
+/// Follow the path of an argument from the target of its value, returning
+/// the target at the end of the path and the steps to reach it.
+fn follow<'s>(root: Target, path: &[fmt_macros::Segment<'s>]) -> Result<(Target, Vec<Step>), Error<'s>>
+{
+    use fmt_macros as p;
+    use index::Lookup;
+
+    let (mut traits, mut compute) = root;
+    let mut steps = Vec::with_capacity(path.len());
+    for segment in path {
+        let span = Span::from_inner(segment.span);
+        // Paths pass through an `Option` to its contents.
+        if let Some((step, inner)) = unwrap(&traits) {
+            steps.push(step);
+            traits = inner;
+        }
+        let field = match (segment.access, traits.get_elements()) {
+            (p::Field(field), _) => field,
+            (access, Some(elements)) => {
+                steps.push(match (access, elements.lookup) {
+                    (p::Index(index), Lookup::Index(get)) => Step::Index(get, index),
+                    (p::Key(key), Lookup::Key(get)) => Step::Key(get, key.to_owned()),
+                    _ => return Err(Error::NotIndexable(span)),
+                });
+                traits = elements.traits();
+                continue;
+            }
+            (_, None) => return Err(Error::NotIndexable(span)),
+        };
+        let fields = match traits.get_fields() {
+            Some(fields) => fields,
+            None => return Err(Error::NoFields(span)),
+        };
+        let idx = if field.starts_with(|c: char| c.is_ascii_digit()) {
+            let idx = field.parse().unwrap_or(usize::MAX);
+            if !(fields.validate_index)(idx) {
+                return Err(Error::BadIndex(idx, span));
+            }
+            idx
+        } else {
+            match (fields.validate_name)(field) {
+                Some(idx) => idx,
+                None => {
+                    let names: Vec<_> = (fields.names)().iter().cloned()
+                        .filter(|name| !name.is_empty())
+                        .collect();
+                    let suggestions = suggest::similar(field, &names);
+                    return Err(Error::BadName(field.into(), span, suggestions));
+                }
+            }
+        };
+        steps.push(Step::Field {
+            get: fields.get,
+            idx: idx,
+            len: (fields.len)(),
+            name: (fields.names)().get(idx).cloned().filter(|name| !name.is_empty()),
+        });
+        let child = (fields.get_child)(idx);
+        traits = child.traits;
+        compute = child.compute;
+    }
+    Ok(((traits, compute), steps))
+}
+
+/// The step to the contents of an `Option`, and their traits, if the traits
+/// are those of an `Option`.
+fn unwrap(traits: &Traits<erase::Opaque>) -> Option<(Step, Traits<erase::Opaque>)> {
+    match traits.get_elements() {
+        Some(elements) => match elements.lookup {
+            index::Lookup::Optional(get) => Some((Step::Unwrap(get), elements.traits())),
+            _ => None,
+        },
+        None => None,
+    }
+}
+
+/// Check a format spec for options which have no effect on the argument it
+/// applies to.
+fn lint(format: &fmt_macros::FormatSpec, idx: usize, kind: Kind, span: Span, warnings: &mut Vec<Warning>) {
+    use fmt_macros as p;
+
+    let flag = |flag: p::Flag| format.flags & (1 << flag as u32) != 0;
+
+    // Only the exponent formats respect the precision of integers.
+    if kind == Kind::Integer && format.precision != p::CountImplied &&
+        format.ty != "e" && format.ty != "E"
+    {
+        let span = format.precision_span.map_or(span, Span::from_inner);
+        warnings.push(Warning::IntegerPrecision { idx: idx, span: span });
+    }
+    if kind == Kind::Text && flag(p::FlagSignPlus) {
+        warnings.push(Warning::TextSign { idx: idx, span: span });
+    }
+    if kind != Kind::Other && format.ty.is_empty() && flag(p::FlagAlternate) {
+        warnings.push(Warning::AlternateDisplay { idx: idx, span: span });
+    }
+    if format.fill.is_some() && flag(p::FlagSignAwareZeroPad) {
+        warnings.push(Warning::ZeroPadFill { idx: idx, span: span });
+    }
 }
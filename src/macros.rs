@@ -14,16 +14,30 @@
 #[macro_export]
 macro_rules! rt_format_args {
     (@[$spec:expr] [$($args:tt)*] $name:tt = $e:expr, $($rest:tt)*) => {
-        rt_format_args!(@[$spec] [$($args)* $crate::Param::named(stringify!($name), &$e),] $($rest)*)
+        rt_format_args!(@[$spec] [$($args)* rt_format_args!(@param $e, named(stringify!($name))),] $($rest)*)
     };
     (@[$spec:expr] [$($args:tt)*] $name:tt = $e:expr) => {
-        rt_format_args!(@[$spec] [$($args)* $crate::Param::named(stringify!($name), &$e),])
+        rt_format_args!(@[$spec] [$($args)* rt_format_args!(@param $e, named(stringify!($name))),])
     };
     (@[$spec:expr] [$($args:tt)*] $e:expr, $($rest:tt)*) => {
-        rt_format_args!(@[$spec] [$($args)* $crate::Param::normal(&$e),] $($rest)*)
+        rt_format_args!(@[$spec] [$($args)* rt_format_args!(@param $e, normal()),] $($rest)*)
     };
     (@[$spec:expr] [$($args:tt)*] $e:expr) => {
-        rt_format_args!(@[$spec] [$($args)* $crate::Param::normal(&$e),])
+        rt_format_args!(@[$spec] [$($args)* rt_format_args!(@param $e, normal()),])
+    };
+    // The formatting traits of each argument are probed here, where its
+    // type is concrete, or detected through specialization with the
+    // `nightly` feature. The `match` keeps any temporary alive for the rest
+    // of the enclosing statement.
+    (@param $e:expr, normal()) => {
+        match &$e {
+            value => $crate::Param::normal_with(value, $crate::__rt_traits!(of value)),
+        }
+    };
+    (@param $e:expr, named($name:expr)) => {
+        match &$e {
+            value => $crate::Param::named_with($name, value, $crate::__rt_traits!(of value)),
+        }
     };
     (@[$spec:expr] [$($args:tt)*]) => {
         $crate::FormatBuf::new(&$spec, &[$($args)*])
@@ -36,6 +50,34 @@ macro_rules! rt_format_args {
     };
 }
 
+/// Build a `Schema` from the types of a list of arguments.
+///
+/// The syntax is that of `rt_format_args!`, without the format string and
+/// with types in place of values: `rt_schema!(&str, width = usize)`. The
+/// formatting traits of each type are recorded as `rt_format_args!` would
+/// record them for a value of that type.
+#[macro_export]
+macro_rules! rt_schema {
+    (@[$($args:tt)*]) => {
+        $crate::Schema::new() $($args)*
+    };
+    (@[$($args:tt)*] $name:ident = $t:ty, $($rest:tt)*) => {
+        rt_schema!(@[$($args)* .named(stringify!($name), $crate::__rt_traits!(type $t))] $($rest)*)
+    };
+    (@[$($args:tt)*] $name:ident = $t:ty) => {
+        rt_schema!(@[$($args)* .named(stringify!($name), $crate::__rt_traits!(type $t))])
+    };
+    (@[$($args:tt)*] $t:ty, $($rest:tt)*) => {
+        rt_schema!(@[$($args)* .arg($crate::__rt_traits!(type $t))] $($rest)*)
+    };
+    (@[$($args:tt)*] $t:ty) => {
+        rt_schema!(@[$($args)* .arg($crate::__rt_traits!(type $t))])
+    };
+    ($($rest:tt)*) => {
+        rt_schema!(@[] $($rest)*)
+    };
+}
+
 /// Format a value of type `String` with a runtime format string.
 ///
 /// The format string should be any type coercible to an `&str`, and will not
@@ -0,0 +1,219 @@
+//! The formatting engine which renders parsed format strings.
+//!
+//! Each argument is formatted through a `Formatter` configured by a
+//! `format_args!` literal chosen from a table of every combination of
+//! alignment, flags, width, and precision, so that every type sees exactly
+//! the options it would have seen from `format!`. Only the fill character
+//! cannot be expressed this way, so an argument with a fill other than a
+//! space is formatted with `FILL` in its place, which is then replaced
+//! wherever it appears in the output. A value which writes `FILL` itself
+//! will see it replaced too, but as a noncharacter it is not expected in
+//! text.
+use std::fmt::{self, Write};
+
+use erase::Argument;
+use fmt_macros::{FlagSignPlus, FlagSignMinus, FlagAlternate, FlagSignAwareZeroPad};
+
+/// The specification for formatting a single placeholder.
+#[derive(Copy, Clone)]
+pub struct Spec {
+    /// The index of the argument to format.
+    pub arg: usize,
+    pub fill: char,
+    pub align: Alignment,
+    pub flags: u32,
+    pub precision: Count,
+    pub width: Count,
+}
+
+impl Spec {
+    /// The spec of a placeholder with no options, such as `{}`.
+    #[inline]
+    pub fn plain(arg: usize) -> Spec {
+        Spec {
+            arg: arg,
+            fill: ' ',
+            align: Alignment::Unknown,
+            flags: 0,
+            precision: Count::Implied,
+            width: Count::Implied,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Alignment {
+    Left,
+    Right,
+    Center,
+    Unknown,
+}
+
+/// A width or precision, resolved to an argument index if not literal.
+#[derive(Copy, Clone)]
+pub enum Count {
+    Is(usize),
+    Param(usize),
+    Implied,
+}
+
+/// A parsed format string and its arguments, rendered by its `Display`
+/// implementation.
+///
+/// If `fmt` is `None`, every argument is formatted in order with no options.
+pub struct Arguments<'a> {
+    pieces: &'a [&'a str],
+    args: &'a [Argument<'a>],
+    fmt: Option<&'a [Spec]>,
+}
+
+impl<'a> Arguments<'a> {
+    #[inline]
+    pub fn new(pieces: &'a [&'a str], args: &'a [Argument<'a>], fmt: Option<&'a [Spec]>) -> Self {
+        Arguments {
+            pieces: pieces,
+            args: args,
+            fmt: fmt,
+        }
+    }
+
+    fn count(&self, count: Count) -> Option<usize> {
+        match count {
+            Count::Is(n) => Some(n),
+            Count::Param(i) => self.args[i].as_usize(),
+            Count::Implied => None,
+        }
+    }
+}
+
+impl<'a> fmt::Display for Arguments<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut pieces = self.pieces.iter();
+        match self.fmt {
+            // Every argument uses the default spec, which is exactly the
+            // spec of the outer `Formatter`.
+            None => for (arg, piece) in self.args.iter().zip(pieces.by_ref()) {
+                f.write_str(piece)?;
+                arg.fmt(f)?;
+            },
+            Some(fmt) => for (spec, piece) in fmt.iter().zip(pieces.by_ref()) {
+                f.write_str(piece)?;
+                let options = Options {
+                    fill: spec.fill,
+                    align: spec.align,
+                    flags: spec.flags,
+                    width: self.count(spec.width),
+                    precision: self.count(spec.precision),
+                };
+                options.write(f, &self.args[spec.arg])?;
+            },
+        }
+        // There may be one trailing piece after the final argument.
+        if let Some(piece) = pieces.next() {
+            f.write_str(piece)?;
+        }
+        Ok(())
+    }
+}
+
+// Select the `format_args!` literal matching a set of `Options`, one part of
+// the spec at a time, then write the argument with it.
+macro_rules! configure {
+    (@align $out:ident $opt:ident $arg:ident) => {
+        match ($opt.align, $opt.fill == ' ') {
+            (Alignment::Left, true) => configure!(@sign $out $opt $arg ["<"]),
+            (Alignment::Right, true) => configure!(@sign $out $opt $arg [">"]),
+            (Alignment::Center, true) => configure!(@sign $out $opt $arg ["^"]),
+            (Alignment::Left, false) => configure!(@sign $out $opt $arg ["\u{FDD0}<"]),
+            (Alignment::Right, false) => configure!(@sign $out $opt $arg ["\u{FDD0}>"]),
+            (Alignment::Center, false) => configure!(@sign $out $opt $arg ["\u{FDD0}^"]),
+            (Alignment::Unknown, _) => configure!(@sign $out $opt $arg []),
+        }
+    };
+    (@sign $out:ident $opt:ident $arg:ident [$($lit:expr),*]) => {
+        if $opt.flag(FlagSignPlus as u32) {
+            configure!(@alternate $out $opt $arg [$($lit,)* "+"])
+        } else if $opt.flag(FlagSignMinus as u32) {
+            configure!(@alternate $out $opt $arg [$($lit,)* "-"])
+        } else {
+            configure!(@alternate $out $opt $arg [$($lit),*])
+        }
+    };
+    (@alternate $out:ident $opt:ident $arg:ident [$($lit:expr),*]) => {
+        if $opt.flag(FlagAlternate as u32) {
+            configure!(@zero $out $opt $arg [$($lit,)* "#"])
+        } else {
+            configure!(@zero $out $opt $arg [$($lit),*])
+        }
+    };
+    (@zero $out:ident $opt:ident $arg:ident [$($lit:expr),*]) => {
+        if $opt.flag(FlagSignAwareZeroPad as u32) {
+            configure!(@width $out $opt $arg [$($lit,)* "0"])
+        } else {
+            configure!(@width $out $opt $arg [$($lit),*])
+        }
+    };
+    (@width $out:ident $opt:ident $arg:ident [$($lit:expr),*]) => {
+        match $opt.width {
+            Some(width) => configure!(@precision $out $opt $arg [$($lit,)* "w$"] [, w = width]),
+            None => configure!(@precision $out $opt $arg [$($lit),*] []),
+        }
+    };
+    (@precision $out:ident $opt:ident $arg:ident [$($lit:expr),*] [$($named:tt)*]) => {
+        match $opt.precision {
+            Some(precision) => configure!(@write $out $arg [$($lit,)* ".p$"] [$($named)* , p = precision]),
+            None => configure!(@write $out $arg [$($lit),*] [$($named)*]),
+        }
+    };
+    (@write $out:ident $arg:ident [$($lit:expr),*] [$($named:tt)*]) => {
+        $out.write_fmt(format_args!(concat!("{arg:" $(, $lit)*, "}"), arg = $arg $($named)*))
+    };
+}
+
+/// The fill character given to the `Formatter` in place of any other than a
+/// space, which must match the literal in `configure!`.
+const FILL: char = '\u{FDD0}';
+
+/// A `Spec` with its counts resolved.
+#[derive(Copy, Clone)]
+struct Options {
+    fill: char,
+    align: Alignment,
+    flags: u32,
+    width: Option<usize>,
+    precision: Option<usize>,
+}
+
+impl Options {
+    fn flag(&self, flag: u32) -> bool {
+        self.flags & (1 << flag) != 0
+    }
+
+    fn write(&self, out: &mut dyn Write, arg: &Argument) -> fmt::Result {
+        if self.fill == ' ' || self.align == Alignment::Unknown {
+            return self.write_configured(out, arg);
+        }
+
+        let mut filled = String::new();
+        self.write_configured(&mut filled, arg)?;
+        for c in filled.chars() {
+            out.write_char(if c == FILL { self.fill } else { c })?;
+        }
+        Ok(())
+    }
+
+    fn write_configured(&self, out: &mut dyn Write, arg: &Argument) -> fmt::Result {
+        let arg = Adapter(arg);
+        configure!(@align out self arg)
+    }
+}
+
+/// Forwards `Display` to the erased formatting function of an argument.
+struct Adapter<'a, 'b: 'a>(&'a Argument<'b>);
+
+impl<'a, 'b> fmt::Display for Adapter<'a, 'b> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
@@ -0,0 +1,129 @@
+//! Checking format strings before any arguments exist.
+use erase::{Compute, Opaque};
+use {compare, parse, validate, Difference, Error, Func, ParseTarget, Step, Traits};
+
+/// The names and formatting traits of a list of arguments, against which
+/// format strings can be checked before any values are available.
+///
+/// A schema is most easily built with the `rt_schema!` macro, which accepts
+/// the types of the arguments in the same shape as `rt_format_args!` accepts
+/// their values:
+///
+/// ```
+/// # #[macro_use] extern crate runtime_fmt;
+/// # fn main() {
+/// let schema = rt_schema!(&str, count = u32);
+/// assert!(schema.check("{} x{count:03}").is_ok());
+/// assert!(schema.check("{} x{count:?} {total}").is_err());
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct Schema {
+    params: Vec<SchemaParam>,
+}
+
+#[derive(Clone)]
+struct SchemaParam {
+    name: Option<&'static str>,
+    traits: Traits<Opaque>,
+}
+
+impl Schema {
+    /// Construct a schema with no arguments.
+    #[inline]
+    pub fn new() -> Schema {
+        Schema { params: Vec::new() }
+    }
+
+    /// Add a nameless argument supporting the given formatting traits.
+    #[inline]
+    pub fn arg<T>(mut self, traits: Traits<T>) -> Schema {
+        self.params.push(SchemaParam { name: None, traits: traits.erase() });
+        self
+    }
+
+    /// Add a named argument supporting the given formatting traits.
+    #[inline]
+    pub fn named<T>(mut self, name: &'static str, traits: Traits<T>) -> Schema {
+        self.params.push(SchemaParam { name: Some(name), traits: traits.erase() });
+        self
+    }
+
+    /// Check a format string against this schema, returning the first error
+    /// found, if any.
+    #[inline]
+    pub fn check<'s>(&self, spec: &'s str) -> Result<(), Error<'s>> {
+        parse(spec, &mut SchemaParse(self), false).map(|_| ())
+    }
+
+    /// Check a format string as `check` does, additionally rejecting it if
+    /// any argument goes unused.
+    #[inline]
+    pub fn check_strict<'s>(&self, spec: &'s str) -> Result<(), Error<'s>> {
+        parse(spec, &mut SchemaParse(self), true).map(|_| ())
+    }
+
+    /// Check a format string against this schema, reporting every error it
+    /// contains.
+    #[inline]
+    pub fn validate<'s>(&self, spec: &'s str) -> Result<(), Vec<Error<'s>>> {
+        validate(spec, &mut SchemaParse(self), false)
+    }
+
+    /// Check a format string as `validate` does, additionally reporting any
+    /// argument which goes unused.
+    #[inline]
+    pub fn validate_strict<'s>(&self, spec: &'s str) -> Result<(), Vec<Error<'s>>> {
+        validate(spec, &mut SchemaParse(self), true)
+    }
+
+    /// Check a format string and its translation against this schema, then
+    /// compare the arguments they use. See `compare` for details.
+    pub fn compare<'s>(&self, source: &'s str, translation: &'s str)
+        -> Result<Vec<Difference>, Error<'s>>
+    {
+        let source = parse(source, &mut SchemaParse(self), false)?;
+        let translation = parse(translation, &mut SchemaParse(self), false)?;
+        Ok(compare(&source.usages, &translation.usages))
+    }
+}
+
+struct SchemaParse<'a>(&'a Schema);
+
+impl<'a, 's> ParseTarget<'s> for SchemaParse<'a> {
+    type Argument = ();
+
+    fn validate_name(&mut self, name: &str) -> Option<usize> {
+        self.0.params.iter().position(|p| p.name == Some(name))
+    }
+
+    fn validate_index(&mut self, index: usize) -> bool {
+        index < self.0.params.len()
+    }
+
+    fn traits(&self, idx: usize) -> Traits<Opaque> {
+        self.0.params[idx].traits
+    }
+
+    fn format<'n>(&mut self, _: usize, _: Vec<Step>, _: Func, _: Option<Compute>, _: Option<&'s str>)
+        -> Result<(), Error<'n>>
+    {
+        Ok(())
+    }
+
+    fn format_usize(&mut self, idx: usize) -> Option<()> {
+        if self.0.params[idx].traits.is_count() { Some(()) } else { None }
+    }
+
+    fn names(&self) -> Vec<&'static str> {
+        self.0.params.iter().filter_map(|p| p.name).collect()
+    }
+
+    fn len(&self) -> usize {
+        self.0.params.len()
+    }
+
+    fn name_of(&self, idx: usize) -> Option<&'static str> {
+        self.0.params[idx].name
+    }
+}
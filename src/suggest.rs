@@ -0,0 +1,57 @@
+//! Suggestions of known names in place of misspelled ones.
+use std::cmp::{min, max};
+use std::mem;
+
+/// The edit distance between two strings, counted in characters, where
+/// insertions, deletions, substitutions, and transpositions of adjacent
+/// characters each count as one edit.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // The distances from the previous two prefixes of `a` to each prefix of
+    // `b`, and from the current prefix of `a`.
+    let mut before: Vec<usize> = Vec::new();
+    let mut prev: Vec<usize> = (0..b.len() + 1).collect();
+    for i in 1..a.len() + 1 {
+        let mut cur = vec![i; b.len() + 1];
+        for j in 1..b.len() + 1 {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            cur[j] = min(prev[j - 1] + cost, min(prev[j], cur[j - 1]) + 1);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                cur[j] = min(cur[j], before[j - 2] + 1);
+            }
+        }
+        before = mem::replace(&mut prev, cur);
+    }
+    prev[b.len()]
+}
+
+/// Find the candidates closest to `name`, provided they are close enough to
+/// plausibly be what was meant. Ties are all returned, in the order given.
+pub fn similar(name: &str, candidates: &[&'static str]) -> Vec<&'static str> {
+    let mut best = max(name.chars().count() / 3, 1);
+    let mut result = Vec::new();
+    for &candidate in candidates {
+        let distance = edit_distance(name, candidate);
+        if distance < best {
+            best = distance;
+            result.clear();
+        }
+        if distance == best && !result.contains(&candidate) {
+            result.push(candidate);
+        }
+    }
+    result
+}
+
+/// Describe a list of suggestions, if there are any.
+pub fn did_you_mean(suggestions: &[&str]) -> Option<String> {
+    match suggestions.len() {
+        0 => None,
+        1 => Some(format!("did you mean {:?}?", suggestions[0])),
+        _ => {
+            let list: Vec<String> = suggestions.iter().map(|s| format!("{:?}", s)).collect();
+            Some(format!("did you mean one of {}?", list.join(", ")))
+        }
+    }
+}
@@ -0,0 +1,149 @@
+extern crate runtime_fmt;
+
+use runtime_fmt::Span;
+use runtime_fmt::ast::*;
+
+fn span(start: usize, end: usize) -> Span {
+    Span { start, end }
+}
+
+fn placeholder(piece: &Piece) -> &Placeholder {
+    match *piece {
+        Piece::Placeholder(ref placeholder) => placeholder,
+        ref other => panic!("expected a placeholder, found {:?}", other),
+    }
+}
+
+#[test]
+fn literals() {
+    let template = Template::parse("a{{b}}c {} d").unwrap();
+    assert_eq!(template.pieces.len(), 3);
+    assert_eq!(template.pieces[0], Piece::Literal(Literal {
+        text: "a{b}c ".to_owned(),
+        span: span(0, 8),
+    }));
+    assert_eq!(template.pieces[2], Piece::Literal(Literal {
+        text: " d".to_owned(),
+        span: span(10, 12),
+    }));
+    assert_eq!(Template::parse("").unwrap().pieces, []);
+}
+
+#[test]
+fn positions() {
+    let template = Template::parse("{} {1} {name} {}").unwrap();
+    let positions: Vec<_> = template.pieces.iter()
+        .filter_map(|piece| match *piece {
+            Piece::Placeholder(ref p) => Some((p.position.clone(), p.position_span, p.span)),
+            Piece::Literal(_) => None,
+        })
+        .collect();
+    assert_eq!(positions, [
+        (Position::Next(0), span(1, 1), span(0, 2)),
+        (Position::Index(1), span(4, 5), span(3, 6)),
+        (Position::Named("name".to_owned()), span(8, 12), span(7, 13)),
+        (Position::Next(1), span(15, 15), span(14, 16)),
+    ]);
+}
+
+#[test]
+fn paths() {
+    let template = Template::parse("{user.address.city} {0.1:?}").unwrap();
+    let first = placeholder(&template.pieces[0]);
+    assert_eq!(first.position, Position::Named("user".to_owned()));
+    assert_eq!(first.position_span, span(1, 5));
+    assert_eq!(first.path, [
+        Segment { access: Access::Field("address".to_owned()), span: span(5, 13) },
+        Segment { access: Access::Field("city".to_owned()), span: span(13, 18) },
+    ]);
+    let second = placeholder(&template.pieces[2]);
+    assert_eq!(second.position, Position::Index(0));
+    assert_eq!(second.path, [Segment { access: Access::Field("1".to_owned()), span: span(22, 24) }]);
+    let template = Template::parse("{items[-1].name} {0[\"Host\"]}").unwrap();
+    assert_eq!(placeholder(&template.pieces[0]).path, [
+        Segment { access: Access::Index(-1), span: span(6, 10) },
+        Segment { access: Access::Field("name".to_owned()), span: span(10, 15) },
+    ]);
+    assert_eq!(placeholder(&template.pieces[2]).path, [
+        Segment { access: Access::Key("Host".to_owned()), span: span(19, 27) },
+    ]);
+    assert!(Template::parse("{items[]}").is_err());
+    assert!(Template::parse("{items[0}").is_err());
+    assert!(Template::parse("{items[\"a]}").is_err());
+    assert!(Template::parse("{user.}").is_err());
+    assert!(Template::parse("{.name}").is_err());
+}
+
+#[test]
+fn format_spec() {
+    let template = Template::parse("{0:*^+#010.3x}").unwrap();
+    assert_eq!(placeholder(&template.pieces[0]).format, FormatSpec {
+        fill: Some('*'),
+        align: Alignment::Center,
+        sign: Some(Sign::Plus),
+        alternate: true,
+        zero_pad: true,
+        width: Count::Is(10),
+        width_span: Some(span(8, 10)),
+        precision: Count::Is(3),
+        precision_span: Some(span(10, 12)),
+        ty: "x".to_owned(),
+        ty_span: Some(span(12, 13)),
+        fallback: None,
+        fallback_span: None,
+    });
+
+    let template = Template::parse("{:w$.*} {:1$.p$?}").unwrap();
+    let first = &placeholder(&template.pieces[0]);
+    assert_eq!(first.position, Position::Next(1));
+    assert_eq!(first.format.width, Count::Name("w".to_owned()));
+    assert_eq!(first.format.precision, Count::Next(0));
+    let second = &placeholder(&template.pieces[2]);
+    assert_eq!(second.position, Position::Next(2));
+    assert_eq!(second.format.width, Count::Param(1));
+    assert_eq!(second.format.precision, Count::Name("p".to_owned()));
+    assert_eq!(second.format.ty, "?");
+    assert_eq!(second.format.sign, None);
+    assert_eq!(second.format.align, Alignment::Unknown);
+
+    let template = Template::parse("{nickname|anonymous} {:-x}").unwrap();
+    let first = &placeholder(&template.pieces[0]).format;
+    assert_eq!(first.fallback, Some("anonymous".to_owned()));
+    assert_eq!(first.fallback_span, Some(span(9, 19)));
+    assert_eq!(first.sign, None);
+    let second = &placeholder(&template.pieces[2]).format;
+    assert_eq!(second.fallback, None);
+    assert_eq!(second.sign, Some(Sign::Minus));
+    assert_eq!(second.ty, "x");
+}
+
+#[test]
+fn syntax_errors() {
+    let errors = Template::parse("{ {0:}").unwrap_err();
+    assert!(!errors.is_empty());
+    assert!(Template::parse("}").unwrap_err()[0].note.is_some());
+}
+
+#[test]
+fn canonical() {
+    fn canonical(spec: &str) -> String {
+        Template::parse(spec).unwrap().to_string()
+    }
+
+    assert_eq!(canonical("{} {}"), canonical("{0} {1}"));
+    assert_eq!(canonical("{} {}"), "{0} {1}");
+    assert_eq!(canonical("{{}} {:}"), "{{}} {0}");
+    assert_eq!(canonical("{name: >5} {:-}"), "{name:>5} {0}");
+    assert_eq!(canonical("{:.*} {:w$.p$?}"), "{1:.0$} {2:w$.p$?}");
+    assert_eq!(canonical("{:0$} {:00$} {:*^+#08.2e}"), "{0:0$} {1:00$} {2:*^+#08.2e}");
+    assert_eq!(canonical("{a.b.0: <3}"), "{a.b.0:<3}");
+    assert_eq!(canonical("{a[-1][\"k\"].b:}"), "{a[-1][\"k\"].b}");
+    assert_eq!(canonical("{name|n/a} {:-#x}"), "{name|n/a} {0:#x}");
+    assert_eq!(canonical("{n|0} {n:-0}"), "{n|0} {n:0}");
+    assert_eq!(canonical("{n|-:} {n|-: >w$}"), "{n|-} {n|-:>w$}");
+
+    for spec in &["", "a{{b", "}}", "{:<<3}", "{x:1$.3$x} {}", "{:.*}{:+.0}"] {
+        let once = canonical(spec);
+        assert_eq!(canonical(&once), once);
+    }
+}
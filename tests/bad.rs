@@ -1,5 +1,7 @@
 #[macro_use] extern crate runtime_fmt;
+#[macro_use] extern crate runtime_fmt_derive;
 
+use runtime_fmt::{FormatBuf, Param, PreparedFormat, Span, Traits};
 use runtime_fmt::Error::*;
 
 macro_rules! err_with {
@@ -17,16 +19,39 @@ macro_rules! err_any {
     }
 }
 
+fn span(start: usize, end: usize) -> Span {
+    Span { start, end }
+}
+
 #[test]
 fn bad_index() {
-    err_with!(BadIndex(0); "{}");
-    err_with!(BadIndex(7); "{7}");
-    err_with!(BadIndex(2); "{} {} {}", "", "");
+    err_with!(BadIndex(0, span(0, 2)); "{}");
+    err_with!(BadIndex(7, span(1, 2)); "{7}");
+    err_with!(BadIndex(2, span(6, 8)); "{} {} {}", "", "");
+}
+
+#[test]
+fn bad_name() {
+    err_with!(BadName("nope".into(), span(5, 9), vec![]); "a = {nope}", a=1);
+    err_with!(BadName("w".into(), span(3, 5), vec![]); "{:>w$}", 1);
 }
 
 #[test]
 fn bad_usize() {
-    err_with!(BadCount(0); "{:.*}", "Not A Usize", "aaaa");
+    err_with!(BadCount(0, span(2, 4)); "{:.*}", "Not A Usize", "aaaa");
+    err_with!(BadCount(1, span(7, 9)); "{nick:>w$}", nick="ann", w=true);
+    err_with!(BadCount(0, span(2, 4)); "{:.*}", 'x', "aaaa");
+
+    #[derive(FormatArgs)]
+    struct Flagged {
+        nick: &'static str,
+        w: bool,
+    }
+
+    match PreparedFormat::<Flagged>::prepare("{nick:>w$}") {
+        Err(BadCount(1, _)) => {}
+        other => panic!("unexpected {:?}", other.map(|_| ())),
+    }
 }
 
 #[test]
@@ -34,11 +59,138 @@ fn bad_syntax() {
     err_any!("{-1}");
 }
 
+#[test]
+fn syntax_spans() {
+    let spans = |spec: &str| match rt_format!(spec) {
+        Err(BadSyntax(errors)) => errors.iter().map(|e| e.span).collect::<Vec<_>>(),
+        other => panic!("unexpected {:?}", other),
+    };
+    assert_eq!(spans("abc }"), [span(4, 5)]);
+    assert_eq!(spans("abc {0"), [span(4, 6)]);
+    assert_eq!(spans("{0é}"), [span(2, 4), span(4, 5)]);
+}
+
+#[test]
+fn element_out_of_range() {
+    let error = |spec: &str| match rt_format!(spec, 1) {
+        Err(BadSyntax(errors)) => (errors[0].description.clone(), errors[0].span),
+        other => panic!("unexpected {:?}", other),
+    };
+    assert_eq!(error("{0[-9223372036854775808]}"), ("index out of range".into(), span(3, 23)));
+    assert_eq!(error("{0[-18446744073709551615]}"), ("index out of range".into(), span(3, 24)));
+    assert_eq!(error("{0[9223372036854775808]}"), ("index out of range".into(), span(3, 22)));
+}
+
 #[test]
 fn bad_format() {
     struct Foo;
 
-    err_with!(NoSuchFormat("q"); "{:q}", "");
-    err_with!(UnsatisfiedFormat { idx: 0, must_implement: "Debug" };
+    err_with!(NoSuchFormat("q".into(), span(2, 3), vec!["?", "e", "E", "o", "p", "b", "x", "X"]); "{:q}", "");
+    err_with!(UnsatisfiedFormat { idx: 0, must_implement: "Debug", span: span(2, 3) };
         "{:?}", Foo);
+    err_with!(UnsatisfiedFormat { idx: 0, must_implement: "Display", span: span(1, 3) };
+        "({})", Foo);
+}
+
+#[test]
+fn error_span() {
+    let spec = "name: {nmae}";
+    let span = rt_format!(spec, name="x").unwrap_err().span().unwrap();
+    assert_eq!(&spec[span.start..span.end], "nmae");
+}
+
+#[test]
+fn validate_all() {
+    #[derive(FormatArgs)]
+    struct Entry {
+        name: &'static str,
+        count: usize,
+    }
+
+    let spec = "{nmae}: {count:.prec$?} {5} }";
+    let errors = PreparedFormat::<Entry>::validate(spec).unwrap_err();
+    let kinds: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+    assert_eq!(kinds, [
+        "unknown name \"nmae\" (did you mean \"name\"?)",
+        "unknown name \"prec\"",
+        "index 5 out of range",
+        "unmatched `}` found (if you intended to print `}`, you can escape it using `}}`)",
+    ]);
+    assert_eq!(errors[1].span(), Some(span(15, 21)));
+
+    assert!(PreparedFormat::<Entry>::validate("{name}: {count:>count$}").is_ok());
+}
+
+#[test]
+fn validate_params() {
+    let x = 1;
+    let params = [Param::named_with("x", &x, Traits::new().display())];
+    assert_eq!(
+        format!("{:?}", FormatBuf::validate("{x:?} {y} {x:q}", &params)),
+        format!("Err({:?})", [
+            UnsatisfiedFormat { idx: 0, must_implement: "Debug", span: span(3, 4) },
+            BadName("y".into(), span(7, 8), vec!["x"]),
+            NoSuchFormat("q".into(), span(13, 14), vec!["?", "e", "E", "o", "p", "b", "x", "X"]),
+        ])
+    );
+}
+
+#[test]
+fn owned_error() {
+    fn check(spec: String) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(rt_format!(spec, x=1).map_err(|e| e.into_owned())?)
+    }
+
+    assert_eq!(check("{x}".to_owned()).unwrap(), "1");
+    let err = check("{y}".to_owned()).unwrap_err();
+    assert_eq!(err.to_string(), "unknown name \"y\" (did you mean \"x\"?)");
+    match *err.downcast::<runtime_fmt::OwnedError>().unwrap() {
+        BadName(ref name, span, _) => assert_eq!((&name[..], span), ("y", Span { start: 1, end: 2 })),
+        ref other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn suggestions() {
+    #[derive(FormatArgs)]
+    struct Entry {
+        name: &'static str,
+        names: usize,
+        count: usize,
+    }
+
+    let errors = PreparedFormat::<Entry>::validate("{nmae} {cuont:>namex$} {zzz} {name:xx}").unwrap_err();
+    let suggestions: Vec<&[&str]> = errors.iter().map(|e| e.suggestions()).collect();
+    assert_eq!(suggestions, [&["name"][..], &["count"], &["name", "names"], &[], &["x"]]);
+    assert_eq!(errors[0].to_string(), "unknown name \"nmae\" (did you mean \"name\"?)");
+    assert_eq!(errors[2].to_string(),
+               "unknown name \"namex\" (did you mean one of \"name\", \"names\"?)");
+
+    err_with!(BadName("wdith".into(), span(3, 9), vec!["width"]); "{:>wdith$}", 1, width=5);
+    err_with!(NoSuchFormat("d".into(), span(2, 3), vec!["?", "e", "E", "o", "p", "b", "x", "X"]); "{:d}", 1);
+}
+
+#[test]
+fn strict_prepared() {
+    #[derive(FormatArgs)]
+    struct Message {
+        user: &'static str,
+        count: usize,
+    }
+
+    assert!(PreparedFormat::<Message>::prepare_strict("{user}: {count}").is_ok());
+    assert!(PreparedFormat::<Message>::prepare_strict("{user:>count$}").is_ok());
+    assert!(PreparedFormat::<Message>::prepare("{user}").is_ok());
+    assert_eq!(
+        format!("{:?}", PreparedFormat::<Message>::prepare_strict("{user}").map(|_| ())),
+        format!("Err({:?})", UnusedArgument { idx: 1, name: Some("count") })
+    );
+    assert_eq!(
+        format!("{:?}", PreparedFormat::<Message>::validate_strict("{usr}")),
+        format!("Err({:?})", [
+            BadName("usr".into(), span(1, 4), vec!["user"]),
+            UnusedArgument { idx: 0, name: Some("user") },
+            UnusedArgument { idx: 1, name: Some("count") },
+        ])
+    );
 }
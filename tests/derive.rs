@@ -0,0 +1,500 @@
+#[macro_use] extern crate runtime_fmt_derive;
+#[macro_use] extern crate runtime_fmt;
+
+use runtime_fmt::{PreparedFormat, Role, Span, Usage};
+
+fn span(start: usize, end: usize) -> Span {
+    Span { start, end }
+}
+
+#[derive(FormatArgs)]
+struct Row {
+    label: &'static str,
+    value: u32,
+    width: usize,
+}
+
+#[test]
+fn usages() {
+    let format = PreparedFormat::<Row>::prepare("{label:>width$}: {value:#x} {label}").unwrap();
+    assert_eq!(format.usages(), [
+        Usage { idx: 0, name: Some("label"), path: vec![], role: Role::Format("Display"), span: span(1, 6) },
+        Usage { idx: 2, name: Some("width"), path: vec![], role: Role::Width, span: span(8, 14) },
+        Usage { idx: 1, name: Some("value"), path: vec![], role: Role::Format("LowerHex"), span: span(18, 23) },
+        Usage { idx: 0, name: Some("label"), path: vec![], role: Role::Format("Display"), span: span(29, 34) },
+    ]);
+}
+
+#[test]
+fn translation_differences() {
+    use runtime_fmt::Difference::*;
+
+    let prepare = |spec| PreparedFormat::<Row>::prepare(spec).unwrap();
+    let source = prepare("{label:>width$}: {value}");
+    assert_eq!(source.differences(&prepare("{value} :{label:>width$}")), []);
+
+    let width = Usage { idx: 2, name: Some("width"), path: vec![], role: Role::Width, span: span(8, 14) };
+    let differences = source.differences(&prepare("{label}: {value:x} {value:x}"));
+    assert_eq!(differences, [
+        Missing(width.clone()),
+        NewRole(Usage { idx: 1, name: Some("value"), path: vec![], role: Role::Format("LowerHex"), span: span(10, 15) }),
+    ]);
+    assert_eq!(differences.iter().map(ToString::to_string).collect::<Vec<_>>(), [
+        "argument \"width\" is not used by the translation",
+        "argument \"value\" is formatted with LowerHex only by the translation",
+    ]);
+
+    assert_eq!(source.differences(&prepare("{label:.width$}")), [
+        MissingCount(width),
+        Missing(Usage { idx: 1, name: Some("value"), path: vec![], role: Role::Format("Display"), span: span(18, 23) }),
+        NewRole(Usage { idx: 2, name: Some("width"), path: vec![], role: Role::Precision, span: span(7, 14) }),
+    ]);
+}
+
+#[derive(FormatArgs)]
+enum Event {
+    Login { user: &'static str, attempts: usize },
+    Logout { user: &'static str },
+    Error(u16, &'static str),
+    Idle,
+}
+
+#[test]
+fn enums() {
+    use runtime_fmt::Error;
+
+    let format = PreparedFormat::<Event>::prepare("{user:>attempts$}").unwrap();
+    assert_eq!(format.format(&Event::Login { user: "ann", attempts: 5 }), "  ann");
+    match format.try_format(&Event::Logout { user: "ann" }) {
+        Err(Error::MissingField { idx: 3, name: Some("attempts") }) => {}
+        other => panic!("unexpected {:?}", other),
+    }
+
+    let format = PreparedFormat::<Event>::prepare("{0} {1:?}").unwrap();
+    assert_eq!(format.format(&Event::Error(404, "gone")), "404 \"gone\"");
+    assert_eq!(
+        format.try_format(&Event::Idle).unwrap_err().to_string(),
+        "field 0 is not present in this value"
+    );
+    assert!(PreparedFormat::<Event>::prepare("{2}").is_err());
+    assert_eq!(
+        PreparedFormat::<Event>::validate("{usr}").unwrap_err()[0].to_string(),
+        "unknown name \"usr\" (did you mean \"user\"?)"
+    );
+    assert_eq!(PreparedFormat::<Event>::prepare("plain").unwrap().format(&Event::Idle), "plain");
+
+    let mut text = String::new();
+    format.write_fmt(&Event::Error(500, "down"), &mut text).unwrap();
+    match format.try_write_fmt(&Event::Idle, &mut text) {
+        Err(Error::MissingField { idx: 0, name: None }) => {}
+        other => panic!("unexpected {:?}", other),
+    }
+    let mut bytes = Vec::new();
+    format.try_write_io(&Event::Error(503, "busy"), &mut bytes).unwrap();
+    assert!(format.try_write_io(&Event::Idle, &mut bytes).is_err());
+    assert!(format.try_print(&Event::Idle).is_err());
+    assert_eq!(text, "500 \"down\"");
+    assert_eq!(bytes, b"503 \"busy\"");
+}
+
+#[test]
+#[should_panic(expected = "field \"user\" is not present in this value")]
+fn enum_missing_field_panics() {
+    PreparedFormat::<Event>::prepare("{user}").unwrap().format(&Event::Idle);
+}
+
+#[test]
+fn field_attributes() {
+    #[derive(FormatArgs)]
+    struct Account {
+        #[format(rename = "user-name", alias = "user", alias = "login")]
+        user_name: &'static str,
+        #[format(skip)]
+        #[allow(dead_code)]
+        password: &'static str,
+        #[format(alias = "id")]
+        number: u32,
+    }
+
+    let account = Account { user_name: "ann", password: "hunter2", number: 7 };
+    let format = |spec| PreparedFormat::<Account>::prepare(spec).map(|f| f.format(&account));
+    assert_eq!(format("{user-name} {user} {login} #{number}{id}").unwrap(), "ann ann ann #77");
+    assert!(format("{user_name}").is_err());
+    assert!(format("{password}").is_err());
+    assert_eq!(
+        PreparedFormat::<Account>::validate("{pasword}").unwrap_err()[0].to_string(),
+        "unknown name \"pasword\""
+    );
+    assert!(PreparedFormat::<Account>::prepare_strict("{login} {id}").is_ok());
+
+    #[derive(FormatArgs)]
+    struct Pair(#[format(skip)] #[allow(dead_code)] &'static str, #[format(rename = "b")] u8);
+    let prepare = PreparedFormat::<Pair>::prepare;
+    assert_eq!(prepare("{0}{b}").unwrap().format(&Pair("a", 2)), "22");
+    assert!(prepare("{1}").is_err());
+}
+
+#[test]
+fn enum_field_attributes() {
+    #[derive(FormatArgs)]
+    enum Message {
+        Text { #[format(rename = "body")] text: &'static str },
+        Image { #[format(alias = "text")] body: &'static str, #[format(skip)] #[allow(dead_code)] size: usize },
+    }
+
+    let format = PreparedFormat::<Message>::prepare("{body}/{text}").unwrap();
+    assert_eq!(format.format(&Message::Text { text: "hi" }), "hi/hi");
+    assert_eq!(format.format(&Message::Image { body: "cat", size: 9 }), "cat/cat");
+    assert!(PreparedFormat::<Message>::prepare("{size}").is_err());
+}
+
+#[test]
+fn flatten() {
+    #[derive(FormatArgs)]
+    struct Header {
+        id: u32,
+        #[format(rename = "source")]
+        from: &'static str,
+    }
+
+    #[derive(FormatArgs)]
+    struct Message {
+        #[format(flatten)]
+        header: Header,
+        body: &'static str,
+        #[format(flatten)]
+        row: Row,
+        id: usize,
+    }
+
+    let message = Message {
+        header: Header { id: 1, from: "ann" },
+        body: "hello",
+        row: Row { label: "x", value: 255, width: 3 },
+        id: 2,
+    };
+    let format = PreparedFormat::<Message>::prepare("{source}#{id}: {body} {value:>width$x}").unwrap();
+    assert_eq!(format.format(&message), "ann#2: hello  ff");
+    assert!(PreparedFormat::<Message>::prepare("{header}").is_err());
+    assert_eq!(
+        PreparedFormat::<Message>::validate("{sorce}").unwrap_err()[0].to_string(),
+        "unknown name \"sorce\" (did you mean \"source\"?)"
+    );
+    assert!(PreparedFormat::<Message>::prepare_strict("{body} {id} {label}{value}{width}{source}").is_err());
+    assert_eq!(
+        PreparedFormat::<Message>::validate_strict("{body} {id} {label}{value}{width}{source}")
+            .unwrap_err()[0].to_string(),
+        "argument \"id\" is never used"
+    );
+}
+
+#[test]
+fn paths() {
+    use runtime_fmt::Error;
+    use runtime_fmt::ast::Access;
+
+    #[derive(FormatArgs)]
+    struct Address {
+        city: &'static str,
+        zip: u32,
+    }
+
+    #[derive(FormatArgs)]
+    struct User {
+        name: &'static str,
+        address: Address,
+        #[format(rename = "last")]
+        event: Event,
+    }
+
+    #[derive(FormatArgs)]
+    struct Pair(User, u8);
+
+    let user = User {
+        name: "ann",
+        address: Address { city: "Oslo", zip: 150 },
+        event: Event::Logout { user: "ann" },
+    };
+    let format = PreparedFormat::<User>::prepare("{name} in {address.city:>5} {address.zip:05}").unwrap();
+    assert_eq!(format.format(&user), "ann in  Oslo 00150");
+    assert_eq!(format.usages()[1].name, Some("address"));
+    assert_eq!(format.usages()[1].path, [Access::Field("city".to_owned())]);
+
+    let source = PreparedFormat::<User>::prepare("{address.city} {name}").unwrap();
+    let translation = PreparedFormat::<User>::prepare("{address.zip} {name}").unwrap();
+    let differences = source.differences(&translation);
+    assert_eq!(differences.iter().map(ToString::to_string).collect::<Vec<_>>(), [
+        "argument \"address.city\" is not used by the translation",
+        "argument \"address.zip\" is not used by the source",
+    ]);
+    assert_eq!(source.differences(&PreparedFormat::<User>::prepare("{name} {address.city}").unwrap()), []);
+
+    let pair = Pair(user, 7);
+    let format = PreparedFormat::<Pair>::prepare("{0.address.city}/{1}").unwrap();
+    assert_eq!(format.format(&pair), "Oslo/7");
+
+    let format = PreparedFormat::<Pair>::prepare("{0.last.user}").unwrap();
+    assert_eq!(format.format(&pair), "ann");
+    let format = PreparedFormat::<Pair>::prepare("{0.last.attempts}").unwrap();
+    match format.try_format(&pair) {
+        Err(Error::MissingField { idx: 3, name: Some("attempts") }) => {}
+        other => panic!("unexpected {:?}", other),
+    }
+
+    let errors = PreparedFormat::<Pair>::validate("{0.adress.city} {0.name.len} {0.3} {0.address.city:x}")
+        .unwrap_err();
+    assert_eq!(errors.iter().map(ToString::to_string).collect::<Vec<_>>(), [
+        "unknown name \"adress\" (did you mean \"address\"?)",
+        "value has no fields",
+        "index 3 out of range",
+        "argument 0 does not implement LowerHex",
+    ]);
+    assert_eq!(errors[0].span(), Some(span(2, 9)));
+    assert_eq!(errors[1].span(), Some(span(23, 27)));
+    assert!(PreparedFormat::<Pair>::prepare("{1.0}").is_err());
+
+    assert_eq!(rt_format!("{0.address.city} {p.1}", &pair.0, p = pair).unwrap(), "Oslo 7");
+}
+
+#[test]
+fn indexing() {
+    use std::collections::HashMap;
+    use runtime_fmt::Error;
+
+    #[derive(FormatArgs)]
+    struct Item {
+        name: &'static str,
+    }
+
+    #[derive(FormatArgs)]
+    struct Batch {
+        ids: Vec<u32>,
+    }
+
+    #[derive(FormatArgs)]
+    struct Request {
+        items: Vec<Item>,
+        tags: &'static [&'static str],
+        headers: HashMap<&'static str, String>,
+    }
+
+    let mut headers = HashMap::new();
+    headers.insert("Host", "example.com".to_owned());
+    let request = Request {
+        items: vec![Item { name: "first" }, Item { name: "last" }],
+        tags: &["a", "b"],
+        headers,
+    };
+    let format = PreparedFormat::<Request>::prepare(
+        "{items[0].name}..{items[-1].name} {tags[1]} {headers[\"Host\"]:?}"
+    ).unwrap();
+    assert_eq!(format.format(&request), "first..last b \"example.com\"");
+
+    let format = PreparedFormat::<Request>::prepare("{items[2].name}").unwrap();
+    match format.try_format(&request) {
+        Err(Error::IndexOutOfRange(2)) => {}
+        other => panic!("unexpected {:?}", other),
+    }
+    let format = PreparedFormat::<Request>::prepare("{headers[\"Accept\"]}").unwrap();
+    assert_eq!(format.try_format(&request).unwrap_err().to_string(), "key \"Accept\" is not present in this value");
+
+    let batch = Batch { ids: vec![4, 8] };
+    assert_eq!(PreparedFormat::<Batch>::prepare("{ids.1}").unwrap().format(&batch), "8");
+    match PreparedFormat::<Batch>::prepare("{ids.5}").unwrap().try_format(&batch) {
+        Err(Error::IndexOutOfRange(5)) => {}
+        other => panic!("unexpected {:?}", other),
+    }
+
+    let errors = PreparedFormat::<Request>::validate("{items[0].nme} {tags[0][0]} {items[\"a\"]} {items[0]}")
+        .unwrap_err();
+    assert_eq!(errors.iter().map(ToString::to_string).collect::<Vec<_>>(), [
+        "unknown name \"nme\" (did you mean \"name\"?)",
+        "value cannot be indexed this way",
+        "value cannot be indexed this way",
+        "argument 0 does not implement Display",
+    ]);
+    assert_eq!(errors[1].span(), Some(span(23, 26)));
+}
+
+#[test]
+fn methods() {
+    #[derive(FormatArgs)]
+    #[format(method = "full_name")]
+    #[format(method = "initial", method = "age")]
+    struct Person {
+        first: &'static str,
+        last: String,
+        born: u32,
+    }
+
+    impl Person {
+        fn full_name(&self) -> String {
+            format!("{} {}", self.first, self.last)
+        }
+
+        fn initial(&self) -> &str {
+            &self.last[..1]
+        }
+
+        fn age(&self) -> u32 {
+            2020 - self.born
+        }
+    }
+
+    #[derive(FormatArgs)]
+    struct Team {
+        lead: Person,
+        #[format(flatten)]
+        deputy: Person,
+    }
+
+    let person = Person { first: "Ann", last: "Lee".to_owned(), born: 1990 };
+    let format = PreparedFormat::<Person>::prepare("{full_name:>8}|{first} {initial}.|{age:x}").unwrap();
+    assert_eq!(format.format(&person), " Ann Lee|Ann L.|1e");
+    assert_eq!(format.usages()[0].name, Some("full_name"));
+    assert_eq!(
+        PreparedFormat::<Person>::validate("{full_nam}").unwrap_err()[0].to_string(),
+        "unknown name \"full_nam\" (did you mean \"full_name\"?)"
+    );
+    assert!(PreparedFormat::<Person>::prepare("{:age$}").is_err());
+    assert!(PreparedFormat::<Person>::prepare("{initial.x}").is_err());
+    assert!(PreparedFormat::<Person>::prepare_strict("{first} {last} {born}").is_ok());
+    assert_eq!(
+        PreparedFormat::<Person>::validate_strict("{first} {last} {full_name}").unwrap_err()[0].to_string(),
+        "argument \"born\" is never used"
+    );
+    let format = PreparedFormat::<Team>::prepare_strict("{lead.age} {first} {last} {born}").unwrap();
+    assert_eq!(format.format(&Team {
+        lead: Person { first: "Cy", last: "Ng".to_owned(), born: 2010 },
+        deputy: Person { first: "Di", last: "Ro".to_owned(), born: 1980 },
+    }), "10 Di Ro 1980");
+
+    let team = Team { lead: person, deputy: Person { first: "Bo", last: "Kim".to_owned(), born: 2000 } };
+    let format = PreparedFormat::<Team>::prepare("{lead.full_name} & {full_name} ({age})").unwrap();
+    assert_eq!(format.format(&team), "Ann Lee & Bo Kim (20)");
+    assert_eq!(rt_format!("{0.initial}{0.age:?}", team.lead).unwrap(), "L30");
+}
+
+#[test]
+fn with_functions() {
+    use std::fmt;
+    use std::time::Duration;
+
+    fn seconds(duration: &Duration, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&duration.as_secs(), f)?;
+        f.write_str("s")
+    }
+
+    mod hex {
+        use std::fmt;
+
+        pub fn bytes(bytes: &[u8; 4], f: &mut fmt::Formatter) -> fmt::Result {
+            for byte in bytes {
+                write!(f, "{:02x}", byte)?;
+            }
+            Ok(())
+        }
+    }
+
+    #[derive(FormatArgs)]
+    struct Job {
+        #[format(with = "seconds")]
+        elapsed: Duration,
+        #[format(with(LowerHex = "hex::bytes", Display = "hex::bytes"))]
+        id: [u8; 4],
+        #[format(with = "seconds")]
+        timeout: Duration,
+    }
+
+    let job = Job {
+        elapsed: Duration::from_millis(2500),
+        id: [0xde, 0xad, 0x0b, 0x0e],
+        timeout: Duration::from_millis(30500),
+    };
+    let format = PreparedFormat::<Job>::prepare("{id:x} ran {elapsed} of {timeout} ({id})").unwrap();
+    assert_eq!(format.format(&job), "dead0b0e ran 2s of 30s (dead0b0e)");
+    // Only the named traits are replaced.
+    assert_eq!(PreparedFormat::<Job>::prepare("{timeout:?}").unwrap().format(&job), "30.5s");
+    assert!(PreparedFormat::<Job>::prepare("{id:X}").is_err());
+}
+
+#[test]
+fn positional() {
+    #[derive(FormatArgs)]
+    #[format(positional)]
+    struct Line {
+        file: &'static str,
+        #[format(skip)]
+        _column: u32,
+        line: u32,
+        width: usize,
+    }
+
+    let line = Line { file: "main.rs", _column: 4, line: 12, width: 9 };
+    let format = PreparedFormat::<Line>::prepare("{}:{:>2$}|{0}:{line}").unwrap();
+    assert_eq!(format.format(&line), "main.rs:       12|main.rs:12");
+    assert_eq!(format.usages()[1].name, Some("line"));
+    assert!(PreparedFormat::<Line>::prepare_strict("{1} {file} {width}").is_ok());
+    assert!(PreparedFormat::<Line>::prepare("{3}").is_err());
+}
+
+#[test]
+fn optional() {
+    #[derive(FormatArgs)]
+    struct Address {
+        city: &'static str,
+    }
+
+    #[derive(FormatArgs)]
+    struct Profile {
+        name: String,
+        nickname: Option<String>,
+        age: Option<u32>,
+        address: Option<Address>,
+    }
+
+    let format = PreparedFormat::<Profile>::prepare(
+        "{name} ({nickname|anonymous}, {age:>3}) from {address.city|nowhere}"
+    ).unwrap();
+    let full = Profile {
+        name: "Ann".to_owned(),
+        nickname: Some("annie".to_owned()),
+        age: Some(30),
+        address: Some(Address { city: "Oslo" }),
+    };
+    assert_eq!(format.format(&full), "Ann (annie,  30) from Oslo");
+    let empty = Profile { name: "Bo".to_owned(), nickname: None, age: None, address: None };
+    assert_eq!(format.format(&empty), "Bo (anonymous,    ) from nowhere");
+    assert_eq!(PreparedFormat::<Profile>::prepare("{age:?}").unwrap().format(&empty), "None");
+    let format = PreparedFormat::<Profile>::prepare("[{nickname|-:>6}] [{age|?:03}]").unwrap();
+    assert_eq!(format.format(&full), "[ annie] [030]");
+    assert_eq!(format.format(&empty), "[     -] [?  ]");
+    assert!(PreparedFormat::<Profile>::prepare("{address.town}").is_err());
+    assert!(PreparedFormat::<Profile>::prepare("{:age$}").is_err());
+}
+
+#[test]
+fn integer_counts() {
+    use runtime_fmt::Error;
+
+    #[derive(FormatArgs)]
+    struct Cell {
+        text: &'static str,
+        width: u16,
+        precision: u8,
+        shift: i32,
+    }
+
+    let format = PreparedFormat::<Cell>::prepare("[{text:^width$.precision$}] [{text:shift$}]").unwrap();
+    let cell = Cell { text: "abcdef", width: 7, precision: 3, shift: 4 };
+    assert_eq!(format.format(&cell), "[  abc  ] [abcdef]");
+    assert_eq!(format.usages()[1].role, Role::Width);
+
+    let cell = Cell { shift: -1, ..cell };
+    match format.try_format(&cell) {
+        Err(Error::CountOutOfRange { idx: 3, name: Some("shift") }) => {}
+        other => panic!("unexpected {:?}", other),
+    }
+    assert_eq!(format.try_format(&cell).unwrap_err().to_string(), "field \"shift\" is out of range for a count");
+    assert!(PreparedFormat::<Cell>::prepare("{:text$}").is_err());
+}
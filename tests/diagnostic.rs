@@ -0,0 +1,58 @@
+#[macro_use] extern crate runtime_fmt;
+
+macro_rules! rendered {
+    ($spec:expr, $($rest:tt)*) => {
+        match rt_format!($spec, $($rest)*) {
+            Ok(s) => panic!("unexpected success: {:?}", s),
+            Err(e) => e.render($spec).to_string(),
+        }
+    }
+}
+
+#[test]
+fn argument_error() {
+    assert_eq!(rendered!("{} {} {}", "a", "b"), "\
+error: index 2 out of range
+  |
+1 | {} {} {}
+  |       ^^");
+}
+
+#[test]
+fn syntax_error_with_note() {
+    assert_eq!(rendered!("Hello, {}}!", "world"), "\
+error: invalid format string: unmatched `}` found
+  |
+1 | Hello, {}}!
+  |          ^
+  |
+  = note: if you intended to print `}`, you can escape it using `}}`");
+}
+
+#[test]
+fn multiline() {
+    let spec = "first line\n\tsecond: {value}\nthird line";
+    assert_eq!(rendered!(spec, name="x"), "\
+error: unknown name \"value\"
+  |
+2 | \tsecond: {value}
+  | \t         ^^^^^");
+}
+
+#[test]
+fn mismatched_spec() {
+    struct Foo;
+    let err = rt_format!("{:?}", Foo).unwrap_err().render("").to_string();
+    assert_eq!(err, "error: argument 0 does not implement Debug");
+}
+
+#[test]
+fn suggestion() {
+    assert_eq!(rendered!("{nmae}", name=1), "\
+error: unknown name \"nmae\"
+  |
+1 | {nmae}
+  |  ^^^^
+  |
+  = help: did you mean \"name\"?");
+}
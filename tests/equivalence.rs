@@ -1,3 +1,6 @@
+// Some cases deliberately mix named arguments into positional slots.
+#![allow(named_arguments_used_positionally)]
+
 #[macro_use] extern crate runtime_fmt;
 
 macro_rules! case {
@@ -30,4 +33,38 @@ fn simple_equivalence() {
     case!("Hex: {:.>4x}", 17);
     case!("{:p}", "Hello");
     case!("{}{}{}", "(A)", "_ _", "(B)");
-}
\ No newline at end of file
+}
+
+#[test]
+fn spec_equivalence() {
+    case!("[{:*^9}]", "mid");
+    case!("[{:-<6}] [{:->6}]", 42, -42);
+    case!("[{:+08.3}]", 2.71);
+    case!("[{:#010x}] [{:#b}]", 255, 5);
+    case!("[{:~>1$}] [{:.*}]", "ab", 5, 1.5);
+    case!("[{:_^12?}]", Some("x"));
+    case!("[{:é<5}]", 'c');
+    case!("[{:*>08}] [{:*<08}] [{:*^08.1}]", "ab", -7, 1.25);
+}
+
+#[test]
+fn fill_equivalence() {
+    use std::fmt;
+    use std::num::{NonZeroU32, Wrapping};
+    use std::time::Duration;
+
+    struct Unpadded;
+
+    impl fmt::Display for Unpadded {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("x")
+        }
+    }
+
+    case!("[{:*>5}]", Unpadded);
+    case!("[{:*>10?}]", Duration::from_millis(1500));
+    case!("[{:*<10?}] [{:*<10?}]", (), vec![1, 2]);
+    case!("[{:*>8?}]", Some(5));
+    case!("[{:*>08}] [{:*>08}]", NonZeroU32::new(5).unwrap(), Wrapping(5));
+    case!("[{:*>8}] [{:é^7}]", Wrapping(5), "ü");
+}
@@ -32,3 +32,230 @@ fn unused_is_fine() {
     t!("2"; rt_format!("{b}", a=1, b=2).unwrap());
     t!("3 1"; rt_format!("{} {a}", 3, 4, a=1, b=2).unwrap());
 }
+
+#[test]
+fn explicit_traits() {
+    use runtime_fmt::{FormatBuf, Param, Traits};
+
+    let x = 255u8;
+    let params = [
+        Param::normal_with(&x, Traits::new().display().lower_hex()),
+        Param::named_with("y", &x, Traits::new().upper_hex()),
+    ];
+    let buf = FormatBuf::new("{0} {0:#x} {y:04X}", &params).unwrap();
+    assert_eq!(buf.format(), "255 0xff 00FF");
+    assert!(FormatBuf::new("{:?}", &params).is_err());
+}
+
+#[test]
+fn strict_unused() {
+    use runtime_fmt::{Error, FormatBuf, Param, Traits};
+
+    let (a, b) = (1, 2);
+    let params = [
+        Param::normal_with(&a, Traits::new().display()),
+        Param::named_with("b", &b, Traits::new().display().count()),
+    ];
+    assert_eq!(FormatBuf::new_strict("{} {b}", &params).unwrap().format(), "1 2");
+    assert_eq!(FormatBuf::new_strict("{0:1$}", &params).unwrap().format(), " 1");
+    match FormatBuf::new_strict("{}", &params) {
+        Err(Error::UnusedArgument { idx: 1, name: Some("b") }) => {}
+        other => panic!("unexpected {:?}", other.map(|buf| buf.format())),
+    }
+    assert!(FormatBuf::new("{}", &params).is_ok());
+
+    let errors = FormatBuf::validate_strict("{c}", &params).unwrap_err();
+    let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+    assert_eq!(messages, [
+        "unknown name \"c\" (did you mean \"b\"?)",
+        "argument 0 is never used",
+        "argument \"b\" is never used",
+    ]);
+}
+
+#[test]
+fn warnings() {
+    use runtime_fmt::{Span, Warning};
+
+    macro_rules! warned {
+        ($($rest:tt)*) => {
+            rt_format_args!($($rest)*).unwrap().warnings().to_vec()
+        }
+    }
+
+    assert_eq!(warned!("{:.2} {:.2e} {:+}", 5, 5, 5), [
+        Warning::IntegerPrecision { idx: 0, span: Span { start: 2, end: 4 } },
+    ]);
+    assert_eq!(warned!("{:+} {:#}", "s", 'c'), [
+        Warning::TextSign { idx: 0, span: Span { start: 0, end: 4 } },
+        Warning::AlternateDisplay { idx: 1, span: Span { start: 5, end: 9 } },
+    ]);
+    assert_eq!(warned!("{:*>08}", 1.5), [
+        Warning::ZeroPadFill { idx: 0, span: Span { start: 0, end: 7 } },
+    ]);
+    assert_eq!(warned!("{:#?} {:#x} {:.1} {:+}", "s", 5, 1.5, 1.5), []);
+    assert_eq!(
+        Warning::IntegerPrecision { idx: 0, span: Span { start: 2, end: 4 } }.to_string(),
+        "precision has no effect on integer argument 0"
+    );
+}
+
+#[test]
+fn usages() {
+    use runtime_fmt::{Role, Span, Usage};
+
+    let usages = rt_format_args!("{:.*} {0:?}", 3, 1.5).unwrap().usages().to_vec();
+    assert_eq!(usages, [
+        Usage { idx: 1, name: None, path: vec![], role: Role::Format("Display"), span: Span { start: 0, end: 5 } },
+        Usage { idx: 0, name: None, path: vec![], role: Role::Precision, span: Span { start: 2, end: 4 } },
+        Usage { idx: 0, name: None, path: vec![], role: Role::Format("Debug"), span: Span { start: 7, end: 8 } },
+    ]);
+}
+
+#[test]
+fn schema() {
+    use runtime_fmt::{Error, Schema, Traits};
+
+    let schema = rt_schema!(&str, u8, width = usize);
+    assert!(schema.check("{} {1:#x} {0:>width$} {:.*}").is_ok());
+    match schema.check("{0:x}") {
+        Err(Error::UnsatisfiedFormat { idx: 0, must_implement: "LowerHex", .. }) => {}
+        other => panic!("unexpected {:?}", other),
+    }
+    match schema.check("{1:0$}") {
+        Err(Error::BadCount(0, _)) => {}
+        other => panic!("unexpected {:?}", other),
+    }
+    assert!(schema.check_strict("{} {}").is_err());
+    assert_eq!(schema.validate("{3} {widht}").unwrap_err().len(), 2);
+    assert!(rt_schema!().check("plain").is_ok());
+
+    let schema = Schema::new().named("x", Traits::<i32>::new().display().count());
+    assert!(schema.check("{x:x$}").is_ok());
+    assert!(schema.check("{x:?}").is_err());
+}
+
+#[test]
+fn schema_compare() {
+    use runtime_fmt::Difference;
+
+    let schema = rt_schema!(&str, n = u32);
+    assert_eq!(schema.compare("{} has {n} items", "{n} items in {}").unwrap(), []);
+    let differences = schema.compare("{} has {n} items", "{n:?} items").unwrap();
+    assert!(matches!(differences[..], [Difference::Missing(_), Difference::NewRole(_)]));
+    assert!(schema.compare("{}", "{m}").is_err());
+}
+
+#[test]
+fn indexing() {
+    use std::collections::{BTreeMap, HashMap};
+    use runtime_fmt::Error;
+
+    let items = vec![10, 20, 30];
+    let tags: &[&str] = &["a", "b"];
+    let pair = [1.5, 2.5];
+    t!("10 30 20"; "{0[0]} {0[-1]} {0[1]}", items);
+    t!("  b"; "{tags[-1]:>3}", tags = tags);
+    t!("2.50"; "{0[1]:.2}", pair);
+    t!("1e"; "{0[0]:x}", [30u8]);
+
+    let mut headers = HashMap::new();
+    headers.insert("Host".to_owned(), "example.com");
+    t!("example.com"; "{h[\"Host\"]}", h = headers);
+    let mut sorted = BTreeMap::new();
+    sorted.insert("a b", vec![1, 2]);
+    t!("[1, 2]"; "{0[\"a b\"]:?}", sorted);
+
+    match rt_format!("{0[3]}", items) {
+        Err(Error::IndexOutOfRange(3)) => {}
+        other => panic!("unexpected {:?}", other),
+    }
+    assert_eq!(rt_format!("{0[-4]}", items).unwrap_err().to_string(), "index -4 is out of range for this value");
+    assert_eq!(
+        rt_format!("{0[\"Port\"]}", headers).unwrap_err().to_string(),
+        "key \"Port\" is not present in this value"
+    );
+    match rt_format!("{0[\"x\"]} {1[0]}", items, 5) {
+        Err(Error::NotIndexable(span)) => assert_eq!(span.start, 2),
+        other => panic!("unexpected {:?}", other),
+    }
+    assert!(rt_format!("{0[0]:x}", tags).is_err());
+    assert!(rt_format!("{0[0]}", headers).is_err());
+}
+
+#[test]
+fn optional() {
+    use runtime_fmt::{Span, Warning};
+
+    let some = Some(42);
+    let none: Option<i32> = None;
+    t!("[  42] []"; "[{0:>4}] [{1}]", some, none);
+    t!("Some(42) -"; "{0:-?} {1|-}", some, none);
+    t!("Some(42) None"; "{:?} {:?}", some, none);
+    t!("2a"; "{:x}", some);
+    t!("[n/a ]"; "[{|n/a }]", none);
+    t!("0 x"; "{0|0} {0|x}", none);
+    t!("[   42] [     ]"; "[{0:-5}] [{1:-5}]", some, none);
+    assert!(rt_format!("{:x}", Some("s")).is_err());
+
+    let warnings = rt_format_args!("{0|none} {1|none}", 5, none).unwrap().warnings().to_vec();
+    assert_eq!(warnings, [Warning::NeedlessFallback { idx: 0, span: Span { start: 2, end: 7 } }]);
+}
+
+#[test]
+fn builtin_format_args() {
+    use runtime_fmt::{Error, PreparedFormat};
+
+    let format = PreparedFormat::<(i32, &str, f64)>::prepare("{1}: {0:>4} ({2:.1})").unwrap();
+    assert_eq!(format.format(&(42, "answer", 0.25)), "answer:   42 (0.2)");
+    assert!(PreparedFormat::<(i32, &str)>::prepare("{2}").is_err());
+    assert!(PreparedFormat::<(i32, &str)>::prepare("{0:x} {1:x}").is_err());
+    assert!(PreparedFormat::<(i32, &str)>::prepare_strict("{1}").is_err());
+
+    let format = PreparedFormat::<[u8; 3]>::prepare("{0}.{1}.{2:02}").unwrap();
+    assert_eq!(format.format(&[10, 0, 7]), "10.0.07");
+    assert!(PreparedFormat::<[u8; 3]>::prepare("{3}").is_err());
+
+    let format = PreparedFormat::<Vec<String>>::prepare("{0} and {2}").unwrap();
+    assert_eq!(format.format(&vec!["a".to_owned(), "b".to_owned(), "c".to_owned()]), "a and c");
+    match format.try_format(&vec!["a".to_owned()]) {
+        Err(Error::IndexOutOfRange(2)) => {}
+        other => panic!("unexpected {:?}", other),
+    }
+
+    let format = PreparedFormat::<&[usize]>::prepare("[{0:>1$}]").unwrap();
+    assert_eq!(format.format(&&[7, 3][..]), "[  7]");
+    t!("b 2"; "{0.1} {1.0}", ("a", "b"), [2]);
+}
+
+#[test]
+fn fill_renders_once() {
+    use std::cell::Cell;
+    use std::fmt;
+
+    struct Counter(Cell<u32>);
+
+    impl fmt::Display for Counter {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            self.0.set(self.0.get() + 1);
+            f.pad(&self.0.get().to_string())
+        }
+    }
+
+    let counter = Counter(Cell::new(0));
+    t!("**1"; "{:*>3}", counter);
+    assert_eq!(counter.0.get(), 1);
+}
+
+#[cfg(feature = "nightly")]
+#[test]
+fn generic_params() {
+    // The traits are detected for the type the function is called with,
+    // not only those its bounds name.
+    fn show<T: std::fmt::Debug>(spec: &str, t: T) -> String {
+        rt_format!(spec, t).unwrap()
+    }
+
+    assert_eq!(show("{:x}", 255), "ff");
+    assert_eq!(show("{:?}", "a"), "\"a\"");
+}
@@ -10,8 +10,7 @@
 
 #![deny(warnings)]
 #![allow(unused_must_use)]
-#![allow(unused_features)]
-#![feature(box_syntax)]
+#![allow(unnecessary_transmutes)]
 
 // modified here to replace standard macros with this crate's macros
 // modified below to add #[test] and comment out calling inner testcases
@@ -36,7 +35,6 @@ macro_rules! writeln {
 
 use std::cell::RefCell;
 use std::fmt::{self, Write};
-use std::usize;
 
 struct A;
 struct B;
@@ -96,9 +94,9 @@ pub fn main() {
     t!(format!("{:?}", "true"), "\"true\"");
     t!(format!("{:?}", "foo\nbar"), "\"foo\\nbar\"");
     t!(format!("{:?}", "foo\n\"bar\"\r\n\'baz\'\t\\qux\\"),
-       r#""foo\n\"bar\"\r\n\'baz\'\t\\qux\\""#);
+       r#""foo\n\"bar\"\r\n'baz'\t\\qux\\""#);
     t!(format!("{:?}", "foo\0bar\x01baz\u{7f}q\u{75}x"),
-       r#""foo\u{0}bar\u{1}baz\u{7f}qux""#);
+       r#""foo\0bar\u{1}baz\u{7f}qux""#);
     t!(format!("{:o}", 10_usize), "12");
     t!(format!("{:x}", 10_usize), "a");
     t!(format!("{:X}", 10_usize), "A");
@@ -179,7 +177,7 @@ pub fn main() {
     t!(format!("{:+10.3e}", -1.2345e6f64), "  -1.234e6");
 
     // Float edge cases
-    t!(format!("{}", -0.0), "0");
+    t!(format!("{}", -0.0), "-0");
     t!(format!("{:?}", -0.0), "-0.0");
     t!(format!("{:?}", 0.0), "0.0");
 
@@ -247,7 +245,7 @@ pub fn main() {
     //test_once();
 
     // make sure that format! doesn't move out of local variables
-    let a: Box<_> = box 3;
+    let a: Box<_> = Box::new(3);
     format!("{}", a);
     format!("{}", a);
 